@@ -1,7 +1,9 @@
+#![allow(clippy::needless_return)]
+
 use std::io::Write;
 
 use clap::Parser;
-use log::info;
+use log::{error, info};
 
 mod config;
 
@@ -59,22 +61,29 @@ fn main() {
     info!("cli settings: {cli:?}");
 
     let lite_server_config = config::get_lite_server_config(&cli.lite_client_config);
-    let mut client = ton_rs_client::client::Client::new(&ton_rs_client::client::Config {
-        lite_server_config,
-        keystore_dir: cli.key_store_dir.to_str().unwrap().to_string(),
-        request_timeout: std::time::Duration::from_secs(cli.request_timeout_seconds),
-        log_level: cli.log_level,
-    });
+    let client = ton_rs_client::client::ClientBuilder::new()
+        .lite_server_config(lite_server_config)
+        .keystore_dir(cli.key_store_dir.to_str().unwrap())
+        .request_timeout(std::time::Duration::from_secs(cli.request_timeout_seconds))
+        .log_level(cli.log_level)
+        .build()
+        .unwrap_or_else(|err| {
+            error!("can't create client: {err}");
+            std::process::exit(1);
+        });
 
     match &cli.command {
         Some(Commands::AccountState { address }) => {
             let fut = client.get_account_state(address);
-            let state = futures::executor::block_on(fut);
+            let state = futures::executor::block_on(fut).unwrap_or_else(|err| {
+                error!("can't get account state: {err}");
+                std::process::exit(1);
+            });
             info!("account state:");
             std::io::stdout()
                 .write_all(serde_json::to_string_pretty(&state).unwrap().as_bytes())
                 .unwrap();
-            print!("\n");
+            println!();
         }
 
         Some(Commands::Transactions {
@@ -89,15 +98,18 @@ fn main() {
                 from_tx_lt.clone(),
                 from_tx_hash.clone(),
                 to_tx_lt.clone(),
-                limit.clone(),
+                *limit,
             );
-            let state = futures::executor::block_on(fut);
+            let state = futures::executor::block_on(fut).unwrap_or_else(|err| {
+                error!("can't get transactions: {err}");
+                std::process::exit(1);
+            });
             info!("transactions:");
             std::io::stdout()
                 .write_all(serde_json::to_string_pretty(&state).unwrap().as_bytes())
                 .unwrap();
             std::io::stdout().flush().unwrap();
-            print!("\n");
+            println!();
         }
 
         None => {
@@ -8,7 +8,9 @@ fn main() {
         .statik(true)
         .probe("openssl")
         .unwrap();
-    println!("cargo:rustc-link-lib=dylib={}", "crypto");
+    println!("cargo:rustc-link-lib=dylib=crypto");
+
+    link_tonlibjson();
 
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     let lib_dir = std::path::PathBuf::from(LIB_DIR).canonicalize().unwrap();
@@ -18,3 +20,27 @@ fn main() {
 
     fs_extra::dir::copy(lib_dir, out_path, &options).unwrap();
 }
+
+fn link_tonlibjson() {
+    let link_static = env::var_os("CARGO_FEATURE_LINK_STATIC").is_some();
+    let link_dynamic = env::var_os("CARGO_FEATURE_LINK_DYNAMIC").is_some();
+    if !link_static && !link_dynamic {
+        return;
+    }
+
+    println!("cargo:rerun-if-env-changed=TONLIBJSON_LIB_DIR");
+    if let Ok(lib_dir) = env::var("TONLIBJSON_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={lib_dir}");
+    }
+
+    if link_static {
+        println!("cargo:rustc-link-lib=static=tonlibjson");
+        let cpp_runtime = match env::var("CARGO_CFG_TARGET_OS").unwrap().as_str() {
+            "macos" | "ios" => "c++",
+            _ => "stdc++",
+        };
+        println!("cargo:rustc-link-lib=dylib={cpp_runtime}");
+    } else {
+        println!("cargo:rustc-link-lib=dylib=tonlibjson");
+    }
+}
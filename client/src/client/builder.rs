@@ -0,0 +1,384 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use crate::{request::KeyStoreType, Error, Result};
+
+use super::{
+    config::parse_lite_servers, health, Client, ClientPool, Config, LoadOptions, PoolOptions,
+    PoolTransport, RawClient, RecordingTransport, RetryPolicy, TonLib, TonLibLog, Transport,
+};
+
+/// Lite-server config of the TON mainnet, bundled at build time.
+pub const MAINNET_CONFIG: &str = include_str!("../../configs/mainnet.json");
+/// Lite-server config of the TON testnet, bundled at build time.
+pub const TESTNET_CONFIG: &str = include_str!("../../configs/testnet.json");
+
+/// Builds a validated `Config` and the `Client` on top of it.
+///
+/// ```ignore
+/// let client = ClientBuilder::mainnet().in_memory_keystore().build()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    lite_server_config: Option<String>,
+    keystore: KeyStoreType,
+    request_timeout: Duration,
+    log_level: i8,
+    tonlib_log: TonLibLog,
+    log_tag_levels: Vec<(String, i32)>,
+    init_retry: RetryPolicy,
+    blockchain_name: String,
+    ignore_cache: bool,
+    use_callbacks_for_network: bool,
+    library: Option<Arc<TonLib>>,
+    load_options: Option<LoadOptions>,
+    healthiest_lite_servers: Option<usize>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            lite_server_config: None,
+            keystore: KeyStoreType::InMemory,
+            request_timeout: Duration::from_secs(10),
+            log_level: 1,
+            tonlib_log: TonLibLog::Forward,
+            log_tag_levels: Vec::new(),
+            init_retry: RetryPolicy::default(),
+            blockchain_name: String::new(),
+            ignore_cache: false,
+            use_callbacks_for_network: false,
+            library: None,
+            load_options: None,
+            healthiest_lite_servers: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Starts without a lite-server config, set one with `lite_server_config`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mainnet() -> Self {
+        Self::new().lite_server_config(MAINNET_CONFIG)
+    }
+
+    pub fn testnet() -> Self {
+        Self::new().lite_server_config(TESTNET_CONFIG)
+    }
+
+    pub fn lite_server_config(mut self, config: impl Into<String>) -> Self {
+        self.lite_server_config = Some(config.into());
+        self
+    }
+
+    /// Keeps keys in `directory`, tonlib creates it if needed.
+    pub fn keystore_dir(mut self, directory: impl Into<String>) -> Self {
+        self.keystore = KeyStoreType::Directory(directory.into());
+        self
+    }
+
+    /// Keeps keys in memory only, this is the default.
+    pub fn in_memory_keystore(mut self) -> Self {
+        self.keystore = KeyStoreType::InMemory;
+        self
+    }
+
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn log_level(mut self, log_level: i8) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    /// Where tonlib logs go, forwarded to the `log` crate by default.
+    pub fn tonlib_log(mut self, tonlib_log: TonLibLog) -> Self {
+        self.tonlib_log = tonlib_log;
+        self
+    }
+
+    /// Overrides the verbosity of one tonlib log tag.
+    pub fn log_tag_level(mut self, tag: impl Into<String>, level: i32) -> Self {
+        self.log_tag_levels.push((tag.into(), level));
+        self
+    }
+
+    pub fn init_retry(mut self, policy: RetryPolicy) -> Self {
+        self.init_retry = policy;
+        self
+    }
+
+    pub fn blockchain_name(mut self, name: impl Into<String>) -> Self {
+        self.blockchain_name = name.into();
+        self
+    }
+
+    pub fn ignore_cache(mut self, ignore_cache: bool) -> Self {
+        self.ignore_cache = ignore_cache;
+        self
+    }
+
+    pub fn use_callbacks_for_network(mut self, use_callbacks: bool) -> Self {
+        self.use_callbacks_for_network = use_callbacks;
+        self
+    }
+
+    /// Uses an already loaded tonlib library instead of the process-wide one.
+    pub fn library(mut self, library: Arc<TonLib>) -> Self {
+        self.library = Some(library);
+        self
+    }
+
+    /// Loads tonlib with `options` on `build` instead of using the process-wide library.
+    pub fn load_options(mut self, options: LoadOptions) -> Self {
+        self.load_options = Some(options);
+        self
+    }
+
+    /// Before `init`, checks every lite server of the config and keeps only the
+    /// `count` least lagging ones.
+    ///
+    /// Needs a tonlib session per lite server, `build_with_transport` ignores it.
+    pub fn healthiest_lite_servers(mut self, count: usize) -> Self {
+        self.healthiest_lite_servers = Some(count);
+        self
+    }
+
+    /// Validates the options and returns the resulting `Config`.
+    pub fn build_config(&self) -> Result<Config> {
+        let lite_server_config = self
+            .lite_server_config
+            .clone()
+            .ok_or_else(|| Error::InvalidInput("lite server config is not set".to_string()))?;
+        parse_lite_servers(&lite_server_config)?;
+
+        if let KeyStoreType::Directory(directory) = &self.keystore {
+            if directory.is_empty() {
+                return Err(Error::InvalidInput(
+                    "keystore directory is empty".to_string(),
+                ));
+            }
+        }
+        if self.request_timeout.is_zero() {
+            return Err(Error::InvalidInput("request timeout is zero".to_string()));
+        }
+        if self.healthiest_lite_servers == Some(0) {
+            return Err(Error::InvalidInput(
+                "healthiest lite servers count is zero".to_string(),
+            ));
+        }
+        self.init_retry.validate()?;
+
+        Ok(Config {
+            lite_server_config,
+            keystore: self.keystore.clone(),
+            request_timeout: self.request_timeout,
+            log_level: self.log_level,
+            tonlib_log: self.tonlib_log.clone(),
+            log_tag_levels: self.log_tag_levels.clone(),
+            init_retry: self.init_retry.clone(),
+            blockchain_name: self.blockchain_name.clone(),
+            ignore_cache: self.ignore_cache,
+            use_callbacks_for_network: self.use_callbacks_for_network,
+        })
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let (config, library) = self.prepare()?;
+        Client::with_transport(&config, RawClient::with_library(library, config.log_level))
+    }
+
+    /// Builds a client recording its tonlib traffic into the cassette at `path`,
+    /// see `ReplayTransport` to play it back.
+    pub fn build_recording(self, path: impl AsRef<Path>) -> Result<Client<RecordingTransport>> {
+        let (config, library) = self.prepare()?;
+        let transport = RawClient::with_library(library, config.log_level);
+        Client::with_transport(&config, RecordingTransport::create(transport, path)?)
+    }
+
+    /// Builds a client balancing requests over `options.size` tonlib sessions.
+    pub fn build_pool(self, options: PoolOptions) -> Result<ClientPool> {
+        let (config, library) = self.prepare()?;
+        Client::with_transport(&config, PoolTransport::new(&config, options, library)?)
+    }
+
+    pub fn build_with_transport<T: Transport>(self, transport: T) -> Result<Client<T>> {
+        Client::with_transport(&self.build_config()?, transport)
+    }
+
+    /// Builds the config and loads the library, selecting the healthiest lite
+    /// servers if asked to.
+    fn prepare(self) -> Result<(Config, Arc<TonLib>)> {
+        let mut config = self.build_config()?;
+        let healthiest_lite_servers = self.healthiest_lite_servers;
+        let library = self.resolve_library()?;
+        if let Some(count) = healthiest_lite_servers {
+            let health = health::check_lite_servers(&config, library.clone())?;
+            config.lite_server_config =
+                health::healthiest_lite_servers(&config.lite_server_config, health, count)?;
+        }
+        return Ok((config, library));
+    }
+
+    fn resolve_library(self) -> Result<Arc<TonLib>> {
+        match (self.library, self.load_options) {
+            (Some(library), _) => Ok(library),
+            (None, Some(options)) => TonLib::load(&options),
+            (None, None) => TonLib::global(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ScriptedTransport;
+
+    const CONFIG: &str = r#"{"liteservers": [{"ip": 0, "port": 1}]}"#;
+
+    fn invalid_input(builder: ClientBuilder) -> String {
+        match builder.build_config() {
+            Err(Error::InvalidInput(message)) => message,
+            result => panic!("unexpected {result:?}"),
+        }
+    }
+
+    #[test]
+    fn presets_select_bundled_configs() {
+        let mainnet = ClientBuilder::mainnet().build_config().unwrap();
+        assert_eq!(mainnet.lite_server_config, MAINNET_CONFIG);
+        let testnet = ClientBuilder::testnet().build_config().unwrap();
+        assert_eq!(testnet.lite_server_config, TESTNET_CONFIG);
+        assert_ne!(MAINNET_CONFIG, TESTNET_CONFIG);
+        assert!(matches!(mainnet.keystore, KeyStoreType::InMemory));
+    }
+
+    #[test]
+    fn builds_config_from_options() {
+        let config = ClientBuilder::new()
+            .lite_server_config(CONFIG)
+            .keystore_dir("/tmp/keys")
+            .request_timeout(Duration::from_secs(3))
+            .log_level(4)
+            .log_tag_level("adnl", 0)
+            .blockchain_name("mainnet")
+            .ignore_cache(true)
+            .build_config()
+            .unwrap();
+        assert_eq!(config.lite_server_config, CONFIG);
+        assert!(matches!(config.keystore, KeyStoreType::Directory(dir) if dir == "/tmp/keys"));
+        assert_eq!(config.request_timeout, Duration::from_secs(3));
+        assert_eq!(config.log_level, 4);
+        assert_eq!(config.log_tag_levels, [("adnl".to_string(), 0)]);
+        assert_eq!(config.blockchain_name, "mainnet");
+        assert!(config.ignore_cache);
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_lite_server_config() {
+        assert_eq!(
+            invalid_input(ClientBuilder::new()),
+            "lite server config is not set"
+        );
+        assert!(invalid_input(ClientBuilder::new().lite_server_config("{")).contains("not json"));
+        assert_eq!(
+            invalid_input(ClientBuilder::new().lite_server_config(r#"{"liteservers": []}"#)),
+            "lite server config has no liteservers"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let builder = ClientBuilder::new().lite_server_config(CONFIG);
+        assert_eq!(
+            invalid_input(builder.clone().request_timeout(Duration::ZERO)),
+            "request timeout is zero"
+        );
+        assert_eq!(
+            invalid_input(builder.clone().keystore_dir("")),
+            "keystore directory is empty"
+        );
+        assert_eq!(
+            invalid_input(builder.clone().healthiest_lite_servers(0)),
+            "healthiest lite servers count is zero"
+        );
+        invalid_input(builder.clone().init_retry(RetryPolicy {
+            max_attempts: 0,
+            ..RetryPolicy::default()
+        }));
+        invalid_input(builder.init_retry(RetryPolicy {
+            backoff_multiplier: 0.0,
+            ..RetryPolicy::default()
+        }));
+    }
+
+    #[test]
+    fn builds_client_on_transport() {
+        let client = ClientBuilder::new()
+            .lite_server_config(CONFIG)
+            .tonlib_log(TonLibLog::Discard)
+            .build_with_transport(ScriptedTransport::new())
+            .unwrap();
+        let init = client
+            .transport()
+            .sent()
+            .into_iter()
+            .find(|request| request["@type"] == "init")
+            .unwrap();
+        assert_eq!(init["options"]["config"]["config"], CONFIG);
+        assert_eq!(
+            init["options"]["keystore_type"]["@type"],
+            "keyStoreTypeInMemory"
+        );
+    }
+
+    #[test]
+    fn restricts_lite_servers_before_init() {
+        let config = ClientBuilder::new()
+            .lite_server_config(
+                r#"{"liteservers": [{"ip": 0, "port": 1}, {"ip": 0, "port": 2}, {"ip": 0, "port": 3}]}"#,
+            )
+            .tonlib_log(TonLibLog::Discard)
+            .healthiest_lite_servers(1)
+            .build_config()
+            .unwrap();
+        let health = [(0, 2), (1, 0), (2, 1)]
+            .map(|(index, lag)| health::LiteServerHealth {
+                index,
+                address: String::new(),
+                last_seqno: Some(10 - lag),
+                info: None,
+                latency: Some(Duration::from_millis(10)),
+                lag: Some(lag),
+                error: None,
+            })
+            .to_vec();
+        let restricted = Config {
+            lite_server_config: health::healthiest_lite_servers(
+                &config.lite_server_config,
+                health,
+                1,
+            )
+            .unwrap(),
+            ..config
+        };
+
+        let client = Client::with_transport(&restricted, ScriptedTransport::new()).unwrap();
+        let init = client
+            .transport()
+            .sent()
+            .into_iter()
+            .find(|request| request["@type"] == "init")
+            .unwrap();
+        let lite_server_config: serde_json::Value =
+            serde_json::from_str(init["options"]["config"]["config"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            lite_server_config["liteservers"],
+            serde_json::json!([{ "ip": 0, "port": 2 }])
+        );
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{types, Error, Result};
+
+use super::{RawClient, Transport};
+
+/// One line of a cassette file.
+///
+/// `@extra` ids are replaced by their order of appearance (`#1`, `#2`, ...) so the
+/// same scenario always records the same cassette.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteEntry {
+    Send(serde_json::Value),
+    Receive(serde_json::Value),
+    Execute {
+        request: serde_json::Value,
+        response: Option<serde_json::Value>,
+    },
+}
+
+impl CassetteEntry {
+    /// Reads a JSONL cassette written by `RecordingTransport`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        return Ok(entries);
+    }
+}
+
+struct Recorder {
+    writer: LineWriter<File>,
+    /// Normalized `@extra` of the requests waiting for an answer.
+    extras: BTreeMap<String, String>,
+    recorded: usize,
+}
+
+impl Recorder {
+    /// Replaces a request `@extra` with the next normalized one when `register`
+    /// is set, otherwise an answer's with its request's one, forgetting it.
+    fn normalize(&mut self, value: &mut serde_json::Value, register: bool) {
+        let extra = match value["@extra"].as_str() {
+            Some(extra) => extra.to_string(),
+            None => return,
+        };
+        let normalized = match register {
+            true => {
+                let recorded = &mut self.recorded;
+                self.extras
+                    .entry(extra)
+                    .or_insert_with(|| {
+                        *recorded += 1;
+                        format!("#{recorded}")
+                    })
+                    .clone()
+            }
+            false => match self.extras.remove(&extra) {
+                Some(normalized) => normalized,
+                None => return,
+            },
+        };
+        value["@extra"] = json!(normalized);
+    }
+
+    /// Writes `entry` with its key material emptied.
+    fn write(&mut self, entry: &CassetteEntry) {
+        let mut entry = entry.clone();
+        match &mut entry {
+            CassetteEntry::Send(value) | CassetteEntry::Receive(value) => {
+                types::redact_secrets(value)
+            }
+            CassetteEntry::Execute { request, response } => {
+                types::redact_secrets(request);
+                if let Some(response) = response {
+                    types::redact_secrets(response);
+                }
+            }
+        }
+        let written = serde_json::to_string(&entry)
+            .map_err(Error::from)
+            .and_then(|line| Ok(writeln!(self.writer, "{line}")?));
+        if let Err(err) = written {
+            log::error!("cassette write error: {err}");
+        }
+    }
+}
+
+/// `Transport` writing all traffic of the wrapped transport to a JSONL cassette,
+/// to be served back by `ReplayTransport`.
+///
+/// Passwords, mnemonics and private keys are recorded empty.
+pub struct RecordingTransport<T: Transport = RawClient> {
+    inner: T,
+    recorder: Mutex<Recorder>,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Records into `path`, truncating it.
+    pub fn create(inner: T, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            inner,
+            recorder: Mutex::new(Recorder {
+                writer: LineWriter::new(File::create(path)?),
+                extras: BTreeMap::new(),
+                recorded: 0,
+            }),
+        })
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send(&self, request: &str) -> Result<()> {
+        let mut value: serde_json::Value = serde_json::from_str(request)?;
+        let mut recorder = self.recorder.lock().unwrap();
+        recorder.normalize(&mut value, true);
+        recorder.write(&CassetteEntry::Send(value));
+        drop(recorder);
+
+        self.inner.send(request)
+    }
+
+    fn receive(&self) -> Option<String> {
+        let response = self.inner.receive()?;
+        match serde_json::from_str(&response) {
+            Ok(mut value) => {
+                let mut recorder = self.recorder.lock().unwrap();
+                recorder.normalize(&mut value, false);
+                recorder.write(&CassetteEntry::Receive(value));
+            }
+            Err(err) => log::error!("not recording invalid response: {err}"),
+        }
+        return Some(response);
+    }
+
+    fn execute(&self, request: &str) -> Result<Option<String>> {
+        let response = self.inner.execute(request)?;
+
+        let mut recorder = self.recorder.lock().unwrap();
+        let mut recorded_request: serde_json::Value = serde_json::from_str(request)?;
+        let extra = recorded_request["@extra"].as_str().map(str::to_string);
+        recorder.normalize(&mut recorded_request, true);
+        let recorded_response = response
+            .as_deref()
+            .map(serde_json::from_str::<serde_json::Value>)
+            .transpose()?
+            .map(|mut value| {
+                recorder.normalize(&mut value, false);
+                value
+            });
+        // the answer may not echo `@extra`
+        if let Some(extra) = extra {
+            recorder.extras.remove(&extra);
+        }
+        recorder.write(&CassetteEntry::Execute {
+            request: recorded_request,
+            response: recorded_response,
+        });
+        return Ok(response);
+    }
+
+    fn timed_out(&self, extra: &str) {
+        self.recorder.lock().unwrap().extras.remove(extra);
+        self.inner.timed_out(extra)
+    }
+}
+
+/// `Transport` answering requests from a cassette, without loading tonlib.
+///
+/// A request is matched to the first unused recorded one equal to it, ignoring
+/// `@extra`, or failing that to the first unused one of the same `@type`. Its recorded
+/// responses are delivered with the live `@extra`, followed by the updates recorded
+/// after it. Unmatched requests are answered with a tonlib `error`.
+pub struct ReplayTransport {
+    entries: Vec<CassetteEntry>,
+    used: Mutex<Vec<bool>>,
+    queue: Mutex<VecDeque<String>>,
+    queue_changed: Condvar,
+    receive_timeout: Duration,
+}
+
+impl ReplayTransport {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(CassetteEntry::read(path)?))
+    }
+
+    pub fn new(entries: Vec<CassetteEntry>) -> Self {
+        let transport = Self {
+            used: Mutex::new(vec![false; entries.len()]),
+            entries,
+            queue: Mutex::new(VecDeque::new()),
+            queue_changed: Condvar::new(),
+            receive_timeout: Duration::from_millis(10),
+        };
+        // updates recorded before the first request
+        transport.enqueue_updates(0);
+        return transport;
+    }
+
+    /// Returns whether every recorded request was replayed.
+    pub fn is_exhausted(&self) -> bool {
+        let used = self.used.lock().unwrap();
+        return self
+            .entries
+            .iter()
+            .zip(used.iter())
+            .all(|(entry, used)| *used || matches!(entry, CassetteEntry::Receive(_)));
+    }
+
+    fn find(&self, request: &serde_json::Value, execute: bool) -> Option<usize> {
+        let mut used = self.used.lock().unwrap();
+        let candidates: Vec<(usize, &serde_json::Value)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !used[*index])
+            .filter_map(|(index, entry)| match (entry, execute) {
+                (CassetteEntry::Send(recorded), false) => Some((index, recorded)),
+                (CassetteEntry::Execute { request, .. }, true) => Some((index, request)),
+                _ => None,
+            })
+            .collect();
+
+        let index = candidates
+            .iter()
+            .find(|(_, recorded)| without_extra(recorded) == without_extra(request))
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .find(|(_, recorded)| recorded["@type"] == request["@type"])
+            })
+            .map(|(index, _)| *index)?;
+        used[index] = true;
+        return Some(index);
+    }
+
+    /// Queues the recorded answers to the request at `index` under the live `extra`.
+    fn enqueue_responses(&self, index: usize, extra: &serde_json::Value) {
+        let recorded_extra = match &self.entries[index] {
+            CassetteEntry::Send(request) => &request["@extra"],
+            _ => return,
+        };
+        if recorded_extra.is_null() {
+            return;
+        }
+
+        let mut used = self.used.lock().unwrap();
+        for (response_index, entry) in self.entries.iter().enumerate() {
+            if let CassetteEntry::Receive(response) = entry {
+                if !used[response_index] && &response["@extra"] == recorded_extra {
+                    used[response_index] = true;
+                    let mut response = response.clone();
+                    response["@extra"] = extra.clone();
+                    self.enqueue(response.to_string());
+                }
+            }
+        }
+    }
+
+    /// Queues the updates recorded from `index` up to the next request.
+    fn enqueue_updates(&self, index: usize) {
+        let mut used = self.used.lock().unwrap();
+        for (update_index, entry) in self.entries.iter().enumerate().skip(index) {
+            match entry {
+                CassetteEntry::Receive(update)
+                    if update["@extra"].is_null() && !used[update_index] =>
+                {
+                    used[update_index] = true;
+                    self.enqueue(update.to_string());
+                }
+                CassetteEntry::Receive(_) => {}
+                _ if update_index > index => break,
+                _ => {}
+            }
+        }
+    }
+
+    fn enqueue(&self, message: String) {
+        self.queue.lock().unwrap().push_back(message);
+        self.queue_changed.notify_one();
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: &str) -> Result<()> {
+        let request: serde_json::Value = serde_json::from_str(request)?;
+        match self.find(&request, false) {
+            Some(index) => {
+                self.enqueue_responses(index, &request["@extra"]);
+                self.enqueue_updates(index);
+            }
+            None => {
+                let mut error = not_recorded(&request);
+                if !request["@extra"].is_null() {
+                    error["@extra"] = request["@extra"].clone();
+                }
+                self.enqueue(error.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&self) -> Option<String> {
+        let queue = self.queue.lock().unwrap();
+        let (mut queue, _) = self
+            .queue_changed
+            .wait_timeout_while(queue, self.receive_timeout, |queue| queue.is_empty())
+            .unwrap();
+        queue.pop_front()
+    }
+
+    fn execute(&self, request: &str) -> Result<Option<String>> {
+        let request: serde_json::Value = serde_json::from_str(request)?;
+        let index = match self.find(&request, true) {
+            Some(index) => index,
+            None => return Ok(Some(not_recorded(&request).to_string())),
+        };
+        let mut response = match &self.entries[index] {
+            CassetteEntry::Execute {
+                response: Some(response),
+                ..
+            } => response.clone(),
+            _ => return Ok(None),
+        };
+        if !response["@extra"].is_null() {
+            response["@extra"] = request["@extra"].clone();
+        }
+        return Ok(Some(response.to_string()));
+    }
+}
+
+fn without_extra(value: &serde_json::Value) -> serde_json::Value {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+        object.remove("@extra");
+    }
+    return value;
+}
+
+fn not_recorded(request: &serde_json::Value) -> serde_json::Value {
+    json!({
+      "@type": "error",
+      "code": 400,
+      "message": format!("no recorded response for {}", request["@type"].as_str().unwrap_or_default())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::client::{Client, ClientBuilder, RetryPolicy, ScriptedTransport, TonLibLog};
+
+    fn builder() -> ClientBuilder {
+        return ClientBuilder::new()
+            .lite_server_config(r#"{"liteservers": [{"ip": 0, "port": 1}]}"#)
+            .tonlib_log(TonLibLog::Discard)
+            .init_retry(RetryPolicy::no_retry());
+    }
+
+    fn scripted() -> ScriptedTransport {
+        let transport = ScriptedTransport::new();
+        transport.on("blocks.getMasterchainInfo", |_| {
+            let block = json!({
+              "@type": "ton.blockIdExt",
+              "workchain": -1,
+              "shard": "-9223372036854775808",
+              "seqno": 42,
+              "root_hash": "",
+              "file_hash": ""
+            });
+            return json!({
+              "@type": "blocks.masterchainInfo",
+              "init": block,
+              "last": block,
+              "state_root_hash": ""
+            });
+        });
+        transport.reply(
+            "createNewKey",
+            json!({ "@type": "key", "public_key": "PuZ", "secret": "c2VjcmV0" }),
+        );
+        return transport;
+    }
+
+    fn scenario<T: Transport>(client: &Client<T>) -> (usize, String) {
+        let info = block_on(client.get_master_chain_info()).unwrap();
+        let key = block_on(client.create_new_key(
+            &types::SecureBytes::new(b"local".to_vec()),
+            &types::SecureBytes::default(),
+        ))
+        .unwrap();
+        return (info.last.seqno, key.public_key);
+    }
+
+    #[test]
+    fn recording_keeps_live_answers() {
+        let path = std::env::temp_dir().join(format!("cassette-live-{}.jsonl", std::process::id()));
+        let transport = RecordingTransport::create(scripted(), &path).unwrap();
+
+        let response = transport
+            .execute(r#"{"@type": "blocks.getMasterchainInfo", "@extra": "live"}"#)
+            .unwrap()
+            .unwrap();
+        let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["@extra"], "live");
+
+        transport
+            .send(r#"{"@type": "blocks.getMasterchainInfo", "@extra": "sent"}"#)
+            .unwrap();
+        let response: serde_json::Value =
+            serde_json::from_str(&transport.receive().unwrap()).unwrap();
+        assert_eq!(response["@extra"], "sent");
+
+        // answered and timed out requests are forgotten
+        transport
+            .send(r#"{"@type": "sync", "@extra": "late"}"#)
+            .unwrap();
+        transport.timed_out("late");
+        assert!(transport.recorder.lock().unwrap().extras.is_empty());
+
+        let extras: Vec<serde_json::Value> = CassetteEntry::read(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| match entry {
+                CassetteEntry::Send(request) => request["@extra"].clone(),
+                CassetteEntry::Receive(response) => response["@extra"].clone(),
+                CassetteEntry::Execute { request, .. } => request["@extra"].clone(),
+            })
+            .collect();
+        assert_eq!(extras, [json!("#1"), json!("#2"), json!("#2"), json!("#3")]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replays_recorded_session() {
+        let path = std::env::temp_dir().join(format!("cassette-{}.jsonl", std::process::id()));
+
+        let recording = builder()
+            .build_with_transport(RecordingTransport::create(scripted(), &path).unwrap())
+            .unwrap();
+        let recorded = scenario(&recording);
+        drop(recording);
+
+        let entries = CassetteEntry::read(&path).unwrap();
+        let sent: Vec<&serde_json::Value> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                CassetteEntry::Send(request) => Some(request),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent[0]["@type"], "init");
+        assert!(sent
+            .iter()
+            .all(|request| request["@extra"].as_str().unwrap().starts_with('#')));
+        let create_key = sent
+            .iter()
+            .find(|request| request["@type"] == "createNewKey")
+            .unwrap();
+        assert_eq!(create_key["local_password"], "");
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("c2VjcmV0"));
+
+        let replay = builder()
+            .build_with_transport(ReplayTransport::open(&path).unwrap())
+            .unwrap();
+        assert_eq!(scenario(&replay), recorded);
+        match block_on(replay.sync()) {
+            Err(Error::TonLib { code, message }) => {
+                assert_eq!(code, 400);
+                assert_eq!(message, "no recorded response for sync");
+            }
+            result => panic!("unexpected {result:?}"),
+        }
+        replay.close().unwrap();
+        assert!(replay.transport().is_exhausted());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,79 +1,221 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use zeroize::Zeroizing;
 
-use crate::{request, types};
+use crate::{request, types, Error, Result};
 
-use super::{raw_receiver::RawReceiver, Config, RawClient};
+use super::{
+    raw_receiver::{CancelHandle, RawReceiver, ResponseFuture, Updates},
+    tonlib_log, Config, RawClient, TonLibLog, Transport,
+};
 
-pub struct Client {
+pub struct Client<T: Transport = RawClient> {
     config: Config,
-    raw_client: Arc<Mutex<RawClient>>,
-    receiver: RawReceiver,
+    transport: Arc<T>,
+    receiver: RawReceiver<T>,
+    closed: AtomicBool,
+    /// Held while tonlib logs are forwarded for `TonLibLog::Forward`.
+    log_forwarder: Mutex<Option<Arc<tonlib_log::Forwarder>>>,
 }
 
 impl Client {
-    pub fn new(config: &Config) -> Self {
-        let raw_client = Arc::new(Mutex::new(RawClient::new(config.log_level)));
-        let receiver = RawReceiver::new(raw_client.clone(), config.request_timeout);
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::with_transport(config, RawClient::new(config.log_level)?)
+    }
+
+    /// Synchronously runs a static tonlib method without a client session,
+    /// like `packAccountAddress` or `getBip39Hints`.
+    pub fn execute_static<R: request::Request>(request: R) -> Result<R::Response> {
+        let req_str = Zeroizing::new(request.serialize(&request::extra()));
+        if !request.is_sensitive() {
+            debug!("execute static request: {}", *req_str);
+        }
+        return decode_response(RawClient::execute_static(&req_str)?);
+    }
+}
 
-        let mut client = Self {
+impl<T: Transport> Client<T> {
+    pub fn with_transport(config: &Config, transport: T) -> Result<Self> {
+        return Self::start(config, transport, true);
+    }
+
+    /// Same as `with_transport`, leaving tonlib's process-wide log settings to the
+    /// client that applied them, for short-lived clients like health probes.
+    pub(crate) fn with_transport_keeping_log(config: &Config, transport: T) -> Result<Self> {
+        return Self::start(config, transport, false);
+    }
+
+    fn start(config: &Config, transport: T, configure_log: bool) -> Result<Self> {
+        config.init_retry.validate()?;
+        let transport = Arc::new(transport);
+        let receiver = RawReceiver::new(transport.clone(), config.request_timeout);
+        receiver.start();
+
+        let client = Self {
             config: config.clone(),
-            raw_client,
+            transport,
             receiver,
+            closed: AtomicBool::new(false),
+            log_forwarder: Mutex::new(None),
         };
-        client.init();
-        return client;
+        if configure_log {
+            if let Err(err) = client.configure_log() {
+                warn!("tonlib log setup error: {err}");
+            }
+        }
+        if let Err(err) = client.init() {
+            client.closed.store(true, Ordering::SeqCst);
+            client.receiver.stop();
+            client.release_log_forwarder();
+            return Err(err);
+        }
+        return Ok(client);
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Closes the tonlib session and stops the receive thread.
+    ///
+    /// Pending and later requests fail with `Error::Closed`. Closing twice is a no-op,
+    /// dropping the client closes it too.
+    pub fn close(&self) -> Result<()> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let result = self
+            .submit(request::Close {}, None)
+            .and_then(futures::executor::block_on);
+        if let Err(err) = &result {
+            warn!("tonlib close error: {err}");
+        }
+        self.receiver.stop();
+        self.release_log_forwarder();
+        info!("client closed");
+
+        return result.map(|_| ());
     }
 
-    pub fn send(&mut self, request: impl request::Request) -> String {
+    pub fn send(&self, request: impl request::Request) -> Result<ResponseFuture> {
+        return self.send_with_timeout(request, None);
+    }
+
+    /// Sends `request`, `timeout` overrides `Config::request_timeout` for this request only.
+    pub fn send_with_timeout(
+        &self,
+        request: impl request::Request,
+        timeout: Option<Duration>,
+    ) -> Result<ResponseFuture> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::Closed);
+        }
+        return self.submit(request, timeout);
+    }
+
+    fn submit(
+        &self,
+        request: impl request::Request,
+        timeout: Option<Duration>,
+    ) -> Result<ResponseFuture> {
         let extra_info = request::extra();
-        let req_str = request.serialize(&extra_info);
+        // secrets of keystore requests are wiped once tonlib copied them
+        let req_str = Zeroizing::new(request.serialize(&extra_info));
 
-        info!("send request: {extra_info}: {req_str}");
+        match request.is_sensitive() {
+            true => info!("send request: {extra_info}: <redacted>"),
+            false => info!("send request: {extra_info}: {}", *req_str),
+        }
 
-        self.receiver.add_task(extra_info.as_str());
+        let response = self.receiver.add_task(extra_info.as_str(), timeout);
 
         debug!("receiver add task: {extra_info}");
 
-        self.raw_client.lock().unwrap().send(&req_str);
+        // dropping the response future removes the task
+        self.transport.send(&req_str)?;
 
-        debug!("raw_client req sent: {extra_info}");
+        debug!("transport req sent: {extra_info}");
 
-        return extra_info;
+        return Ok(response);
+    }
+
+    /// Returns a stream of tonlib `update*` events, like sync progress.
+    ///
+    /// Every subscriber receives every update published after it subscribed.
+    pub fn subscribe_updates(&self) -> Updates {
+        return self.receiver.subscribe();
+    }
+
+    /// Sends `request` and decodes the answer into `R::Response`.
+    pub fn call<R: request::Request>(
+        &self,
+        request: R,
+    ) -> impl futures::Future<Output = Result<R::Response>> {
+        return decode(self.send(request));
+    }
+
+    /// Same as `call`, but fails with `Error::Timeout` after `timeout` instead of
+    /// `Config::request_timeout`.
+    pub fn call_with_timeout<R: request::Request>(
+        &self,
+        request: R,
+        timeout: Duration,
+    ) -> impl futures::Future<Output = Result<R::Response>> {
+        return decode(self.send_with_timeout(request, Some(timeout)));
+    }
+
+    /// Same as `call`, and also returns a handle to cancel the request while it's pending.
+    pub fn call_cancellable<R: request::Request>(
+        &self,
+        request: R,
+        timeout: Option<Duration>,
+    ) -> (
+        CancelHandle,
+        impl futures::Future<Output = Result<R::Response>>,
+    ) {
+        let response = self.send_with_timeout(request, timeout);
+        let cancel_handle = match &response {
+            Ok(response) => response.cancel_handle(),
+            Err(_) => CancelHandle::completed(),
+        };
+        return (cancel_handle, decode(response));
+    }
+
+    /// Synchronously runs `request` on this client's tonlib session, bypassing the receiver.
+    pub fn execute<R: request::Request>(&self, request: R) -> Result<R::Response> {
+        let req_str = Zeroizing::new(request.serialize(&request::extra()));
+        if !request.is_sensitive() {
+            debug!("execute request: {}", *req_str);
+        }
+        return decode_response(self.transport.execute(&req_str)?);
     }
 
     pub fn get_account_state(
-        &mut self,
+        &self,
         address: &str,
-    ) -> impl futures::Future<Output = Option<types::AccountState>> {
-        let extra = self.send(request::AccountState {
+    ) -> impl futures::Future<Output = Result<types::AccountState>> {
+        return self.call(request::AccountState {
             address: address.to_owned(),
         });
-        let response = self.receiver.receive(extra.as_str());
-        return async move {
-            let result = response.await;
-            match result {
-                Ok(response) => {
-                    let result = serde_json::from_value(response).unwrap();
-                    return Some(result);
-                }
-                Err(err) => {
-                    error!("get_account_state error: {err}");
-                    return None;
-                }
-            }
-        };
     }
 
     pub async fn get_transactions(
-        &mut self,
+        &self,
         address: &str,
         from_transaction_lt: Option<String>,
         from_transaction_hash: Option<String>,
         to_transaction_lt: Option<String>,
         limit: Option<usize>,
-    ) -> Vec<types::Transaction> {
+    ) -> Result<Vec<types::Transaction>> {
         const DEFAULT_LIMIT: usize = 10;
 
         let txs_limit = limit.unwrap_or(DEFAULT_LIMIT);
@@ -83,11 +225,7 @@ impl Client {
         let mut from_hash = from_transaction_hash.clone();
 
         if from_transaction_lt.is_none() || from_transaction_hash.is_none() {
-            let maybe_account_state = self.get_account_state(address).await;
-            if maybe_account_state.is_none() {
-                return Vec::new();
-            }
-            let account_state = maybe_account_state.unwrap();
+            let account_state = self.get_account_state(address).await?;
             if from_transaction_lt.is_none() {
                 _ = from_lt.replace(account_state.last_transaction_id.lt.clone());
             }
@@ -99,114 +237,1532 @@ impl Client {
         let mut all_transactions = Vec::with_capacity(txs_limit);
         let mut reach_lt = false;
         while !reach_lt && all_transactions.len() < txs_limit {
-            let extra = self.send(request::Transactions {
-                address: address.to_owned(),
-                from_transaction_lt: from_lt.clone(),
-                from_transaction_hash: from_hash.clone(),
-            });
+            let transactions = self
+                .call(request::Transactions {
+                    address: address.to_owned(),
+                    from_transaction_lt: from_lt.clone(),
+                    from_transaction_hash: from_hash.clone(),
+                })
+                .await?;
 
-            match self.receiver.receive(&extra).await {
-                Ok(response) => {
-                    let transactions: types::Transactions =
-                        serde_json::from_value(response).unwrap();
-                    let txs = transactions.transactions;
-                    if txs.is_empty() {
-                        break;
-                    }
-                    for tx in txs {
-                        if tx.transaction_id.lt == to_lt {
-                            reach_lt = true;
-                            continue;
-                        }
-                        all_transactions.push(tx);
-                    }
-                    if let Some(next) = transactions.previous_transaction_id {
-                        from_lt.replace(next.lt);
-                        from_hash.replace(next.hash);
-                    }
-                }
-                Err(err) => {
-                    error!("get_transactions error: {err}");
-                    break;
+            let txs = transactions.transactions;
+            if txs.is_empty() {
+                break;
+            }
+            for tx in txs {
+                if tx.transaction_id.lt == to_lt {
+                    reach_lt = true;
+                    continue;
                 }
-            };
+                all_transactions.push(tx);
+            }
+            if let Some(next) = transactions.previous_transaction_id {
+                from_lt.replace(next.lt);
+                from_hash.replace(next.hash);
+            }
         }
 
-        return all_transactions;
+        return Ok(all_transactions);
     }
 
-    pub async fn get_master_chain_info(&mut self) -> Option<serde_json::Value> {
-        let resp = self.send(request::MasterChainInfo {});
-        match self.receiver.receive(&resp).await {
-            Ok(response) => {
-                let result = serde_json::from_value(response).unwrap();
-                return Some(result);
-            }
-            Err(err) => {
-                error!("master_chain_info error: {err}");
-                return None;
-            }
-        };
+    /// Broadcasts an external message given as a serialized BoC.
+    pub async fn send_boc(&self, boc: &[u8]) -> Result<()> {
+        if boc.is_empty() {
+            return Err(Error::InvalidInput("message boc is empty".to_string()));
+        }
+        self.call(request::SendMessage { body: boc.to_vec() })
+            .await?;
+        return Ok(());
     }
 
-    pub async fn sync(&mut self) {
-        let resp = self.send(request::SyncClient {});
-        match self.receiver.receive(&resp).await {
-            Ok(response) => {
-                info!("sync success, response: {response}");
-            }
-            Err(err) => {
-                error!("sync client error: {err}");
-            }
-        };
+    /// Broadcasts an external message given as a serialized BoC and returns
+    /// the base64 hash of the message.
+    pub async fn send_boc_return_hash(&self, boc: &[u8]) -> Result<String> {
+        if boc.is_empty() {
+            return Err(Error::InvalidInput("message boc is empty".to_string()));
+        }
+        let info = self
+            .call(request::SendMessageReturnHash { body: boc.to_vec() })
+            .await?;
+        return Ok(info.hash);
+    }
+
+    /// Loads the contract at `address`, the returned id is valid until `smc_forget`.
+    pub async fn smc_load(&self, address: &str) -> Result<i64> {
+        let info = self
+            .call(request::SmcLoad {
+                address: address.to_owned(),
+            })
+            .await?;
+        return Ok(info.id);
+    }
+
+    pub async fn smc_forget(&self, id: i64) -> Result<()> {
+        self.call(request::SmcForget { id }).await?;
+        return Ok(());
+    }
+
+    /// Runs the get-method `method` of a loaded contract.
+    ///
+    /// A failing method isn't an error, check `SmcRunResult::exit_code`.
+    pub async fn run_get_method(
+        &self,
+        id: i64,
+        method: &str,
+        stack: Vec<types::TvmStackEntry>,
+    ) -> Result<types::SmcRunResult> {
+        return self
+            .call(request::SmcRunGetMethod {
+                id,
+                method: request::SmcMethodId::Name(method.to_owned()),
+                stack,
+            })
+            .await;
+    }
+
+    /// Runs the get-method with numeric id `method_id` of a loaded contract.
+    pub async fn run_get_method_by_id(
+        &self,
+        id: i64,
+        method_id: i32,
+        stack: Vec<types::TvmStackEntry>,
+    ) -> Result<types::SmcRunResult> {
+        return self
+            .call(request::SmcRunGetMethod {
+                id,
+                method: request::SmcMethodId::Number(method_id),
+                stack,
+            })
+            .await;
+    }
+
+    /// Loads the contract at `address`, runs `method` and forgets the contract.
+    pub async fn call_get_method(
+        &self,
+        address: &str,
+        method: &str,
+        stack: Vec<types::TvmStackEntry>,
+    ) -> Result<types::SmcRunResult> {
+        let id = self.smc_load(address).await?;
+        let result = self.run_get_method(id, method, stack).await;
+        if let Err(err) = self.smc_forget(id).await {
+            warn!("can't forget contract {id}: {err}");
+        }
+        return result;
+    }
+
+    pub async fn get_master_chain_info(&self) -> Result<types::MasterChainInfo> {
+        return self.call(request::MasterChainInfo {}).await;
+    }
+
+    /// Returns the time, version and capabilities of the lite server tonlib talks to.
+    pub async fn get_lite_server_info(&self) -> Result<types::LiteServerInfo> {
+        return self.call(request::LiteServerGetInfo {}).await;
+    }
+
+    pub async fn sync(&self) -> Result<types::BlockId> {
+        let block = self.call(request::SyncClient {}).await?;
+        info!("sync success, last block: {block:?}");
+        return Ok(block);
     }
 
     pub async fn lookup_block(
-        &mut self,
+        &self,
         workchain: i32,
         shard: String,
         seqno: Option<usize>,
         lt: Option<String>,
         unix_time: Option<usize>,
-    ) -> Result<types::BlockId, String> {
-        assert!(seqno.is_some() || lt.is_some() || unix_time.is_some());
-
-        let resp = self.send(request::LookupBlock {
-            workchain,
-            shard,
-            seqno,
-            lt,
-            utime: unix_time,
+    ) -> Result<types::BlockId> {
+        if seqno.is_none() && lt.is_none() && unix_time.is_none() {
+            return Err(Error::InvalidInput(
+                "lookup_block requires seqno, lt or unix_time".to_string(),
+            ));
+        }
+
+        return self
+            .call(request::LookupBlock {
+                workchain,
+                shard,
+                seqno,
+                lt,
+                utime: unix_time,
+            })
+            .await;
+    }
+
+    /// Returns the shard blocks referenced by the masterchain block `master_block`.
+    pub async fn get_shards(&self, master_block: &types::BlockId) -> Result<Vec<types::BlockId>> {
+        let shards = self
+            .call(request::Shards {
+                id: master_block.clone(),
+            })
+            .await?;
+        return Ok(shards.shards);
+    }
+
+    pub async fn get_block_header(&self, block: &types::BlockId) -> Result<types::BlockHeader> {
+        return self.call(request::BlockHeader { id: block.clone() }).await;
+    }
+
+    /// Returns every shard block committed by masterchain block `master_seqno`, that is
+    /// the blocks since the shard blocks of the previous masterchain block, ordered by
+    /// workchain, shard and seqno.
+    pub async fn get_new_shard_blocks(&self, master_seqno: usize) -> Result<Vec<types::BlockId>> {
+        let master_block = self.lookup_master_block(master_seqno).await?;
+        let shards = self.get_shards(&master_block).await?;
+        let known: HashSet<types::BlockId> = match master_seqno {
+            0 => HashSet::new(),
+            _ => {
+                let prev_block = self.lookup_master_block(master_seqno - 1).await?;
+                self.get_shards(&prev_block).await?.into_iter().collect()
+            }
+        };
+
+        let mut new_blocks = HashSet::new();
+        let mut pending = shards;
+        while let Some(block) = pending.pop() {
+            // zero states aren't committed by any masterchain block
+            if block.seqno == 0 || known.contains(&block) || new_blocks.contains(&block) {
+                continue;
+            }
+            let header = self.get_block_header(&block).await?;
+            pending.extend(header.prev_blocks);
+            new_blocks.insert(block);
+        }
+
+        let mut new_blocks: Vec<types::BlockId> = new_blocks.into_iter().collect();
+        new_blocks.sort_by_key(|block| {
+            let shard = block.shard.parse::<i64>().unwrap_or_default() as u64;
+            (block.workchain, shard, block.seqno)
         });
-        match self.receiver.receive(&resp).await {
-            Ok(response) => {
-                let resp: types::BlockId = serde_json::from_value(response).unwrap();
-                return Ok(resp);
+        return Ok(new_blocks);
+    }
+
+    /// Returns every transaction of `block`, fetching as many pages as needed.
+    pub async fn get_block_transactions(
+        &self,
+        block: &types::BlockId,
+    ) -> Result<Vec<types::AccountTransactionId>> {
+        const PAGE_SIZE: usize = 256;
+
+        let mut all_transactions = Vec::new();
+        let mut after_tx = None;
+        loop {
+            let page = self
+                .call(request::BlockTransactions {
+                    id: block.clone(),
+                    count: PAGE_SIZE,
+                    after_tx: after_tx.take(),
+                })
+                .await?;
+
+            if page.incomplete {
+                // an empty page would send the same request again
+                let last = page.transactions.last().ok_or_else(empty_page_error)?;
+                after_tx = Some(last.clone());
+            }
+            all_transactions.extend(page.transactions);
+            if !page.incomplete {
+                break;
+            }
+        }
+
+        return Ok(all_transactions);
+    }
+
+    /// Returns the full transactions of `block`, fetching as many pages as needed.
+    pub async fn get_block_transactions_ext(
+        &self,
+        block: &types::BlockId,
+    ) -> Result<Vec<types::Transaction>> {
+        const PAGE_SIZE: usize = 256;
+
+        let mut all_transactions = Vec::new();
+        let mut after_tx = None;
+        loop {
+            let page = self
+                .call(request::BlockTransactionsExt {
+                    id: block.clone(),
+                    count: PAGE_SIZE,
+                    after_tx: after_tx.take(),
+                })
+                .await?;
+
+            if page.incomplete {
+                let last = page.transactions.last().ok_or_else(empty_page_error)?;
+                let cursor = last.account_transaction_id().ok_or_else(|| {
+                    Error::InvalidInput(
+                        "block transaction without account address, can't paginate".to_string(),
+                    )
+                })?;
+                after_tx = Some(cursor);
             }
-            Err(err) => {
-                let msg = format!("lookup_block error: {err}");
-                error!("{msg}");
-                return Err(msg);
+            all_transactions.extend(page.transactions);
+            if !page.incomplete {
+                break;
             }
+        }
+
+        return Ok(all_transactions);
+    }
+
+    /// Returns config parameter `id` of `block`, or of the last known block.
+    pub async fn get_config_param(
+        &self,
+        id: u32,
+        block: Option<&types::BlockId>,
+    ) -> Result<types::ConfigParam> {
+        let request = request::GetConfigParam { param: id };
+        let info = match block {
+            Some(block) => {
+                self.call(request::WithBlock {
+                    id: block.clone(),
+                    request,
+                })
+                .await?
+            }
+            None => self.call(request).await?,
         };
+        return types::ConfigParam::decode(id, info.config.to_cell()?);
+    }
+
+    /// Returns every config parameter of `block`, or of the last known block.
+    pub async fn get_config_all(
+        &self,
+        block: Option<&types::BlockId>,
+    ) -> Result<BTreeMap<u32, types::ConfigParam>> {
+        let request = request::GetConfigAll {};
+        let info = match block {
+            Some(block) => {
+                self.call(request::WithBlock {
+                    id: block.clone(),
+                    request,
+                })
+                .await?
+            }
+            None => self.call(request).await?,
+        };
+        let root = info.config.to_cell()?;
+        return types::ConfigParam::decode_all(&root);
+    }
+
+    /// Resolves the TON DNS `name`, like `foundation.ton`.
+    ///
+    /// Resolution continues at next resolvers, up to `ttl` of them, until records of
+    /// `category` are found. Next resolvers are followed here, tonlib resolves a
+    /// single hop per request: each one is asked for the part of the name left once
+    /// the suffix its parent resolved is removed.
+    pub async fn resolve_dns(
+        &self,
+        name: &str,
+        category: types::DnsCategory,
+        ttl: i32,
+    ) -> Result<Vec<types::DnsEntry>> {
+        let mut resolver = None;
+        let mut name = name.to_owned();
+        let mut ttl = ttl;
+        loop {
+            let resolved = self
+                .call(request::DnsResolve {
+                    account_address: resolver.clone(),
+                    name: name.clone(),
+                    category,
+                    ttl: 0,
+                })
+                .await?;
+
+            let next = resolved
+                .entries
+                .iter()
+                .find_map(|entry| match &entry.entry {
+                    types::DnsEntryData::NextResolver { resolver }
+                        if category != types::DnsCategory::NextResolver =>
+                    {
+                        Some((entry.name.clone(), resolver.account_address.clone()))
+                    }
+                    _ => None,
+                });
+            let (resolved_name, next_resolver) = match next {
+                Some(next) if ttl > 0 => next,
+                _ => return Ok(resolved.entries),
+            };
+            name = match unresolved_part(&name, &resolved_name)? {
+                Some(unresolved) => unresolved,
+                // the next resolver itself is the answer
+                None => return Ok(resolved.entries),
+            };
+            ttl -= 1;
+            resolver = Some(next_resolver);
+        }
+    }
+
+    /// Creates a key in the keystore, protected by `local_password`.
+    ///
+    /// `mnemonic_password` is needed again to import the key from its mnemonic words.
+    pub async fn create_new_key(
+        &self,
+        local_password: &types::SecureBytes,
+        mnemonic_password: &types::SecureBytes,
+    ) -> Result<types::Key> {
+        return self
+            .call(request::CreateNewKey {
+                local_password: local_password.clone(),
+                mnemonic_password: mnemonic_password.clone(),
+                random_extra_seed: types::SecureBytes::default(),
+            })
+            .await;
+    }
+
+    /// Returns the 24 mnemonic words of a key.
+    pub async fn export_key(&self, input_key: &types::InputKey) -> Result<types::ExportedKey> {
+        return self
+            .call(request::ExportKey {
+                input_key: input_key.clone(),
+            })
+            .await;
+    }
+
+    pub async fn export_pem_key(
+        &self,
+        input_key: &types::InputKey,
+        key_password: &types::SecureBytes,
+    ) -> Result<types::ExportedPemKey> {
+        return self
+            .call(request::ExportPemKey {
+                input_key: input_key.clone(),
+                key_password: key_password.clone(),
+            })
+            .await;
+    }
+
+    pub async fn export_encrypted_key(
+        &self,
+        input_key: &types::InputKey,
+        key_password: &types::SecureBytes,
+    ) -> Result<types::ExportedEncryptedKey> {
+        return self
+            .call(request::ExportEncryptedKey {
+                input_key: input_key.clone(),
+                key_password: key_password.clone(),
+            })
+            .await;
+    }
+
+    pub async fn export_unencrypted_key(
+        &self,
+        input_key: &types::InputKey,
+    ) -> Result<types::ExportedUnencryptedKey> {
+        return self
+            .call(request::ExportUnencryptedKey {
+                input_key: input_key.clone(),
+            })
+            .await;
+    }
+
+    /// Imports a key from its mnemonic words, protecting it with `local_password`.
+    pub async fn import_key(
+        &self,
+        local_password: &types::SecureBytes,
+        mnemonic_password: &types::SecureBytes,
+        exported_key: &types::ExportedKey,
+    ) -> Result<types::Key> {
+        return self
+            .call(request::ImportKey {
+                local_password: local_password.clone(),
+                mnemonic_password: mnemonic_password.clone(),
+                exported_key: exported_key.clone(),
+            })
+            .await;
+    }
+
+    pub async fn import_pem_key(
+        &self,
+        local_password: &types::SecureBytes,
+        key_password: &types::SecureBytes,
+        exported_key: &types::ExportedPemKey,
+    ) -> Result<types::Key> {
+        return self
+            .call(request::ImportPemKey {
+                local_password: local_password.clone(),
+                key_password: key_password.clone(),
+                exported_key: exported_key.clone(),
+            })
+            .await;
+    }
+
+    pub async fn import_encrypted_key(
+        &self,
+        local_password: &types::SecureBytes,
+        key_password: &types::SecureBytes,
+        exported_key: &types::ExportedEncryptedKey,
+    ) -> Result<types::Key> {
+        return self
+            .call(request::ImportEncryptedKey {
+                local_password: local_password.clone(),
+                key_password: key_password.clone(),
+                exported_encrypted_key: exported_key.clone(),
+            })
+            .await;
+    }
+
+    pub async fn import_unencrypted_key(
+        &self,
+        local_password: &types::SecureBytes,
+        exported_key: &types::ExportedUnencryptedKey,
+    ) -> Result<types::Key> {
+        return self
+            .call(request::ImportUnencryptedKey {
+                local_password: local_password.clone(),
+                exported_unencrypted_key: exported_key.clone(),
+            })
+            .await;
+    }
+
+    pub async fn delete_key(&self, key: &types::Key) -> Result<()> {
+        self.call(request::DeleteKey { key: key.clone() }).await?;
+        return Ok(());
+    }
+
+    /// Removes every key from the keystore.
+    pub async fn delete_all_keys(&self) -> Result<()> {
+        self.call(request::DeleteAllKeys {}).await?;
+        return Ok(());
     }
 
-    fn init(&mut self) {
-        let init_extra = self.send(request::Init {
-            lite_server_config: self.config.lite_server_config.clone(),
-            key_store_directory: self.config.keystore_dir.clone(),
+    /// Re-encrypts a key with `new_local_password`, the returned key replaces the old one.
+    pub async fn change_local_password(
+        &self,
+        input_key: &types::InputKey,
+        new_local_password: &types::SecureBytes,
+    ) -> Result<types::Key> {
+        return self
+            .call(request::ChangeLocalPassword {
+                input_key: input_key.clone(),
+                new_local_password: new_local_password.clone(),
+            })
+            .await;
+    }
+
+    /// Returns the basechain address of a wallet deployed with `initial_account_state`.
+    pub async fn get_account_address(
+        &self,
+        initial_account_state: &types::InitialAccountState,
+    ) -> Result<String> {
+        let address = self
+            .call(request::GetAccountAddress {
+                initial_account_state: initial_account_state.clone(),
+                revision: 0,
+                workchain_id: types::BASECHAIN_WORKCHAIN,
+            })
+            .await?;
+        return Ok(address.account_address);
+    }
+
+    /// Prepares a query sending `messages` from the wallet at `address`, the query
+    /// is kept by tonlib until `query_forget`.
+    ///
+    /// tonlib refuses bounceable messages to accounts that aren't deployed yet unless
+    /// `allow_send_to_uninited` is set, the funds would bounce back.
+    pub async fn create_query(
+        &self,
+        input_key: &types::InputKey,
+        address: &str,
+        messages: Vec<types::Message>,
+        allow_send_to_uninited: bool,
+        initial_account_state: Option<&types::InitialAccountState>,
+    ) -> Result<types::QueryInfo> {
+        if messages.is_empty() {
+            return Err(Error::InvalidInput("query has no messages".to_string()));
+        }
+        return self
+            .call(request::CreateQuery {
+                private_key: input_key.clone(),
+                address: address.to_owned(),
+                timeout: 0,
+                messages,
+                allow_send_to_uninited,
+                initial_account_state: initial_account_state.cloned(),
+            })
+            .await;
+    }
+
+    pub async fn query_send(&self, id: i64) -> Result<()> {
+        self.call(request::QuerySend { id }).await?;
+        return Ok(());
+    }
+
+    /// Estimates the fees of a query, `ignore_chksig` for queries made with `InputKey::Fake`.
+    pub async fn query_estimate_fees(
+        &self,
+        id: i64,
+        ignore_chksig: bool,
+    ) -> Result<types::QueryFees> {
+        return self
+            .call(request::QueryEstimateFees { id, ignore_chksig })
+            .await;
+    }
+
+    pub async fn query_get_info(&self, id: i64) -> Result<types::QueryInfo> {
+        return self.call(request::QueryGetInfo { id }).await;
+    }
+
+    pub async fn query_forget(&self, id: i64) -> Result<()> {
+        self.call(request::QueryForget { id }).await?;
+        return Ok(());
+    }
+
+    /// Sends `messages` from the wallet of `input_key`, deploying the wallet first
+    /// if needed, and returns the sent query.
+    ///
+    /// The transfer is only broadcast, wait for the wallet transaction with
+    /// `QueryInfo::body_hash` to know it went through. See `create_query` for
+    /// `allow_send_to_uninited`.
+    pub async fn transfer(
+        &self,
+        input_key: &types::InputKey,
+        wallet: &types::InitialAccountState,
+        messages: Vec<types::Message>,
+        allow_send_to_uninited: bool,
+    ) -> Result<types::QueryInfo> {
+        let address = self.get_account_address(wallet).await?;
+        let info = self
+            .create_query(
+                input_key,
+                &address,
+                messages,
+                allow_send_to_uninited,
+                Some(wallet),
+            )
+            .await?;
+        let sent = self.query_send(info.id).await;
+        if let Err(err) = self.query_forget(info.id).await {
+            warn!("can't forget query {}: {err}", info.id);
+        }
+        sent?;
+        return Ok(info);
+    }
+
+    async fn lookup_master_block(&self, seqno: usize) -> Result<types::BlockId> {
+        return self
+            .lookup_block(
+                types::MASTERCHAIN_WORKCHAIN,
+                types::MASTERCHAIN_SHARD.to_string(),
+                Some(seqno),
+                None,
+                None,
+            )
+            .await;
+    }
+
+    /// Applies `Config::tonlib_log`, `log_level` and `log_tag_levels`.
+    ///
+    /// These are process-wide tonlib settings, the last configured client wins.
+    fn configure_log(&self) -> Result<()> {
+        let log_stream = match &self.config.tonlib_log {
+            TonLibLog::Stderr => None,
+            TonLibLog::Discard => Some(request::LogStream::Empty),
+            TonLibLog::File {
+                path,
+                max_file_size,
+            } => Some(request::LogStream::File {
+                path: path.clone(),
+                max_file_size: *max_file_size,
+            }),
+            TonLibLog::Forward => {
+                let forwarder = tonlib_log::forwarder()?;
+                let log_stream = request::LogStream::File {
+                    path: forwarder.path().to_string_lossy().into_owned(),
+                    max_file_size: forwarder.max_file_size(),
+                };
+                *self.log_forwarder.lock().unwrap() = Some(forwarder);
+                Some(log_stream)
+            }
+        };
+        if let Some(log_stream) = log_stream {
+            self.execute(request::SetLogStream { log_stream })?;
+        }
+
+        self.execute(request::SetLogVerbosityLevel {
+            new_verbosity_level: self.config.log_level as i32,
+        })?;
+        for (tag, level) in &self.config.log_tag_levels {
+            self.execute(request::SetLogTagVerbosityLevel {
+                tag: tag.clone(),
+                new_verbosity_level: *level,
+            })?;
+        }
+        return Ok(());
+    }
+
+    /// Lets the forwarder stop, pointing tonlib back to stderr if no other client
+    /// forwards its logs.
+    fn release_log_forwarder(&self) {
+        let forwarder = match self.log_forwarder.lock().unwrap().take() {
+            Some(forwarder) => forwarder,
+            None => return,
+        };
+        tonlib_log::release(forwarder, || {
+            let reset = self.execute(request::SetLogStream {
+                log_stream: request::LogStream::Default,
+            });
+            if let Err(err) = reset {
+                warn!("tonlib log stream reset error: {err}");
+            }
         });
-        let fut = self.receiver.receive(init_extra.as_str());
-        self.receiver.start();
-        match futures::executor::block_on(fut) {
-            Ok(response) => {
-                info!("init success: {response}");
+    }
+
+    fn init(&self) -> Result<()> {
+        let policy = &self.config.init_retry;
+        let started = std::time::Instant::now();
+        let mut backoff = policy.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut timeout = self.config.request_timeout;
+            if let Some(deadline) = policy.deadline {
+                timeout = timeout.min(deadline.saturating_sub(started.elapsed()));
+            }
+
+            let fut = self.call_with_timeout(
+                request::Init {
+                    lite_server_config: self.config.lite_server_config.clone(),
+                    key_store: self.config.keystore.clone(),
+                    blockchain_name: self.config.blockchain_name.clone(),
+                    ignore_cache: self.config.ignore_cache,
+                    use_callbacks_for_network: self.config.use_callbacks_for_network,
+                },
+                timeout,
+            );
+            let err = match futures::executor::block_on(fut) {
+                Ok(response) => {
+                    info!("init success: {response}");
+                    return Ok(());
+                }
+                Err(err) => err,
+            };
+
+            let out_of_time = policy
+                .deadline
+                .is_some_and(|deadline| started.elapsed().saturating_add(backoff) >= deadline);
+            if attempt >= policy.max_attempts || out_of_time || !err.is_retryable() {
+                error!("init client error: {err}, giving up after {attempt} attempts");
+                return Err(Error::Init {
+                    attempts: attempt,
+                    source: Box::new(err),
+                });
             }
-            Err(msg) => {
-                error!("init client error: {msg}, trying to re-init after 1s");
-                std::thread::sleep(std::time::Duration::from_secs(1));
-                self.init()
+
+            warn!("init client error: {err}, trying to re-init after {backoff:?}");
+            std::thread::sleep(backoff);
+            backoff = policy.next_backoff(backoff);
+        }
+    }
+}
+
+async fn decode<R: serde::de::DeserializeOwned>(response: Result<ResponseFuture>) -> Result<R> {
+    match response?.await {
+        Ok(response) => {
+            let result = serde_json::from_value(response)?;
+            return Ok(result);
+        }
+        Err(err) => {
+            error!("request error: {err}");
+            return Err(err);
+        }
+    }
+}
+
+/// Returns the labels of `name` in front of `resolved`, the suffix a resolver
+/// handled, `None` when nothing is left.
+fn unresolved_part(name: &str, resolved: &str) -> Result<Option<String>> {
+    let invalid = || {
+        Error::Decode(format!(
+            "next resolver for {resolved:?} doesn't match a suffix of {name:?}"
+        ))
+    };
+    let rest = name.strip_suffix(resolved).ok_or_else(invalid)?;
+    if rest.is_empty() {
+        return Ok(None);
+    }
+    if resolved.is_empty() {
+        return Err(invalid());
+    }
+    let unresolved = match resolved.starts_with('.') {
+        true => rest,
+        // the suffix must start at a label
+        false => rest.strip_suffix('.').ok_or_else(invalid)?,
+    };
+    return Ok(Some(unresolved.to_string()));
+}
+
+fn empty_page_error() -> Error {
+    Error::Decode("incomplete block transactions page without transactions".to_string())
+}
+
+fn decode_response<R: serde::de::DeserializeOwned>(response: Option<String>) -> Result<R> {
+    let response = Zeroizing::new(response.ok_or_else(|| Error::TonLib {
+        code: 0,
+        message: "empty execute response".to_string(),
+    })?);
+    let value: serde_json::Value = serde_json::from_str(&response)?;
+    if value["@type"].as_str() == Some("error") {
+        return Err(Error::from_tonlib(&value));
+    }
+    return Ok(serde_json::from_value(value)?);
+}
+
+impl<T: Transport> Drop for Client<T> {
+    fn drop(&mut self) {
+        if let Err(err) = self.close() {
+            error!("close client on drop error: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::{executor::block_on, StreamExt};
+    use serde_json::json;
+
+    use super::*;
+    use crate::client::{ClientBuilder, RetryPolicy, ScriptedTransport};
+
+    const ACCOUNT: &str = "0:3333333333333333333333333333333333333333333333333333333333333333";
+
+    fn client(transport: ScriptedTransport) -> Client<ScriptedTransport> {
+        return ClientBuilder::new()
+            .lite_server_config(r#"{"liteservers": [{"ip": 0, "port": 1}]}"#)
+            .request_timeout(Duration::from_secs(5))
+            .tonlib_log(TonLibLog::Discard)
+            .init_retry(RetryPolicy::no_retry())
+            .build_with_transport(transport)
+            .unwrap();
+    }
+
+    fn block(seqno: usize) -> serde_json::Value {
+        return json!({
+          "@type": "ton.blockIdExt",
+          "workchain": -1,
+          "shard": "-9223372036854775808",
+          "seqno": seqno,
+          "root_hash": "",
+          "file_hash": ""
+        });
+    }
+
+    fn block_id() -> types::BlockId {
+        return serde_json::from_value(block(1)).unwrap();
+    }
+
+    fn sent_of(client: &Client<ScriptedTransport>, request_type: &str) -> Vec<serde_json::Value> {
+        return client
+            .transport()
+            .sent()
+            .into_iter()
+            .filter(|request| request["@type"] == request_type)
+            .collect();
+    }
+
+    /// Answers `request_type` with `pages`, in order.
+    fn paginate(transport: &ScriptedTransport, request_type: &str, pages: Vec<serde_json::Value>) {
+        let pages = Arc::new(Mutex::new(pages.into_iter()));
+        transport.on(request_type, move |_| {
+            return pages.lock().unwrap().next().unwrap_or_default();
+        });
+    }
+
+    fn short_tx(lt: usize) -> serde_json::Value {
+        return json!({
+          "@type": "blocks.shortTxId",
+          "mode": 135,
+          "account": "MzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzM=",
+          "lt": lt.to_string(),
+          "hash": format!("hash{lt}")
+        });
+    }
+
+    fn tx(lt: usize) -> serde_json::Value {
+        let message = json!({
+          "source": { "account_address": "" },
+          "destination": { "account_address": ACCOUNT },
+          "body_hash": "",
+          "created_lt": "0",
+          "fwd_fee": "0",
+          "ihr_fee": "0",
+          "value": "0"
+        });
+        return json!({
+          "@type": "raw.transaction",
+          "address": { "account_address": ACCOUNT },
+          "transaction_id": { "lt": lt.to_string(), "hash": format!("hash{lt}") },
+          "data": "",
+          "fee": "0",
+          "other_fee": "0",
+          "storage_fee": "0",
+          "utime": 0,
+          "in_msg": message,
+          "out_msgs": []
+        });
+    }
+
+    fn page(
+        request_type: &str,
+        incomplete: bool,
+        transactions: Vec<serde_json::Value>,
+    ) -> serde_json::Value {
+        return json!({
+          "@type": request_type,
+          "id": block(1),
+          "req_count": 256,
+          "incomplete": incomplete,
+          "transactions": transactions
+        });
+    }
+
+    fn shard_block(workchain: i32, shard: &str, seqno: usize) -> serde_json::Value {
+        return json!({
+          "@type": "ton.blockIdExt",
+          "workchain": workchain,
+          "shard": shard,
+          "seqno": seqno,
+          "root_hash": format!("{workchain}:{shard}:{seqno}"),
+          "file_hash": ""
+        });
+    }
+
+    #[test]
+    fn call_decodes_response() {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "blocks.getMasterchainInfo",
+            json!({
+              "@type": "blocks.masterchainInfo",
+              "init": block(0),
+              "last": block(42),
+              "state_root_hash": ""
+            }),
+        );
+        let client = client(transport);
+
+        let info = block_on(client.get_master_chain_info()).unwrap();
+        assert_eq!(info.last.seqno, 42);
+        assert_eq!(sent_of(&client, "init").len(), 1);
+    }
+
+    #[test]
+    fn call_maps_tonlib_errors() {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "blocks.getMasterchainInfo",
+            json!({ "@type": "error", "code": 500, "message": "LITE_SERVER_NOTREADY" }),
+        );
+        let client = client(transport);
+
+        match block_on(client.get_master_chain_info()) {
+            Err(Error::TonLib { code, message }) => {
+                assert_eq!(code, 500);
+                assert_eq!(message, "LITE_SERVER_NOTREADY");
             }
+            result => panic!("unexpected {result:?}"),
+        }
+        assert!(matches!(
+            block_on(client.sync()),
+            Err(Error::TonLib { code: 400, .. })
+        ));
+    }
+
+    #[test]
+    fn init_error_fails_construction() {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "init",
+            json!({ "@type": "error", "code": 400, "message": "invalid config" }),
+        );
+        let result = ClientBuilder::new()
+            .lite_server_config(r#"{"liteservers": [{"ip": 0, "port": 1}]}"#)
+            .tonlib_log(TonLibLog::Discard)
+            .build_with_transport(transport);
+        assert!(matches!(result, Err(Error::Init { attempts: 1, .. })));
+    }
+
+    #[test]
+    fn call_times_out() {
+        let transport = ScriptedTransport::new();
+        transport.on("blocks.getMasterchainInfo", |_| serde_json::Value::Null);
+        let client = client(transport);
+
+        let result = block_on(
+            client.call_with_timeout(request::MasterChainInfo {}, Duration::from_millis(100)),
+        );
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+
+    #[test]
+    fn call_cancelled() {
+        let transport = ScriptedTransport::new();
+        transport.on("blocks.getMasterchainInfo", |_| serde_json::Value::Null);
+        let client = client(transport);
+
+        let (cancel_handle, response) = client.call_cancellable(request::MasterChainInfo {}, None);
+        assert!(cancel_handle.cancel());
+        assert!(matches!(block_on(response), Err(Error::Cancelled)));
+        assert!(!cancel_handle.cancel());
+    }
+
+    #[test]
+    fn subscribers_receive_updates() {
+        let client = client(ScriptedTransport::new());
+        let mut first = client.subscribe_updates();
+        let mut second = client.subscribe_updates();
+
+        client.transport().push(json!({
+          "@type": "updateSyncState",
+          "sync_state": {
+            "@type": "syncStateInProgress",
+            "from_seqno": 1,
+            "to_seqno": 10,
+            "current_seqno": 5
+          }
+        }));
+        for updates in [&mut first, &mut second] {
+            match block_on(updates.next()) {
+                Some(types::Update::SyncState(types::SyncState::InProgress {
+                    current_seqno,
+                    ..
+                })) => assert_eq!(current_seqno, 5),
+                update => panic!("unexpected {update:?}"),
+            }
+        }
+
+        // closing ends the streams
+        client.close().unwrap();
+        assert!(block_on(first.next()).is_none());
+    }
+
+    #[test]
+    fn close_fails_pending_requests() {
+        let transport = ScriptedTransport::new();
+        transport.on("blocks.getMasterchainInfo", |_| serde_json::Value::Null);
+        let client = client(transport);
+
+        let pending = client.call(request::MasterChainInfo {});
+        client.close().unwrap();
+        assert!(matches!(block_on(pending), Err(Error::Closed)));
+        assert!(matches!(
+            block_on(client.get_master_chain_info()),
+            Err(Error::Closed)
+        ));
+        assert_eq!(sent_of(&client, "close").len(), 1);
+
+        // closing again doesn't reach tonlib
+        client.close().unwrap();
+        assert_eq!(sent_of(&client, "close").len(), 1);
+    }
+
+    #[test]
+    fn block_transactions_follow_pages() {
+        let transport = ScriptedTransport::new();
+        paginate(
+            &transport,
+            "blocks.getTransactions",
+            vec![
+                page("blocks.transactions", true, vec![short_tx(1), short_tx(2)]),
+                page("blocks.transactions", false, vec![short_tx(3)]),
+            ],
+        );
+        let client = client(transport);
+
+        let transactions = block_on(client.get_block_transactions(&block_id())).unwrap();
+        let lts: Vec<usize> = transactions.iter().map(|tx| tx.lt).collect();
+        assert_eq!(lts, [1, 2, 3]);
+
+        let requests = sent_of(&client, "blocks.getTransactions");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["mode"], 7);
+        assert_eq!(requests[1]["mode"], 7 + 128);
+        assert_eq!(requests[1]["after"]["lt"], 2);
+    }
+
+    #[test]
+    fn block_transactions_ext_follow_pages() {
+        let transport = ScriptedTransport::new();
+        paginate(
+            &transport,
+            "blocks.getTransactionsExt",
+            vec![
+                page("blocks.transactionsExt", true, vec![tx(1)]),
+                page("blocks.transactionsExt", true, vec![tx(2)]),
+                page("blocks.transactionsExt", false, vec![]),
+            ],
+        );
+        let client = client(transport);
+
+        let transactions = block_on(client.get_block_transactions_ext(&block_id())).unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        let requests = sent_of(&client, "blocks.getTransactionsExt");
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1]["after"]["lt"], 1);
+        assert_eq!(requests[2]["after"]["lt"], 2);
+        assert_eq!(
+            requests[2]["after"]["account"],
+            "MzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzM="
+        );
+    }
+
+    #[test]
+    fn block_transactions_reject_empty_incomplete_page() {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "blocks.getTransactions",
+            page("blocks.transactions", true, vec![]),
+        );
+        let client = client(transport);
+
+        assert!(matches!(
+            block_on(client.get_block_transactions(&block_id())),
+            Err(Error::Decode(_))
+        ));
+        assert_eq!(sent_of(&client, "blocks.getTransactions").len(), 1);
+    }
+
+    #[test]
+    fn send_boc_rejects_empty_message() {
+        let client = client(ScriptedTransport::new());
+        assert!(matches!(
+            block_on(client.send_boc(&[])),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(matches!(
+            block_on(client.send_boc_return_hash(&[])),
+            Err(Error::InvalidInput(_))
+        ));
+        assert!(sent_of(&client, "raw.sendMessage").is_empty());
+        assert!(sent_of(&client, "raw.sendMessageReturnHash").is_empty());
+    }
+
+    #[test]
+    fn send_boc_sends_base64_body() {
+        let transport = ScriptedTransport::new();
+        transport.reply("raw.sendMessage", json!({ "@type": "ok" }));
+        transport.reply(
+            "raw.sendMessageReturnHash",
+            json!({
+              "@type": "raw.extMessageInfo",
+              "hash": "Sh8ZFoYMLRQqk8Hk3rVHwIb8dQw0S6aJhaJX+CQrpV8="
+            }),
+        );
+        let client = client(transport);
+        let boc = [0xb5, 0xee, 0x9c, 0x72, 0x01];
+
+        block_on(client.send_boc(&boc)).unwrap();
+        let hash = block_on(client.send_boc_return_hash(&boc)).unwrap();
+        assert_eq!(hash, "Sh8ZFoYMLRQqk8Hk3rVHwIb8dQw0S6aJhaJX+CQrpV8=");
+
+        assert_eq!(sent_of(&client, "raw.sendMessage")[0]["body"], "te6ccgE=");
+        assert_eq!(
+            sent_of(&client, "raw.sendMessageReturnHash")[0]["body"],
+            "te6ccgE="
+        );
+    }
+
+    #[test]
+    fn send_boc_reports_rejected_message() {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "raw.sendMessage",
+            json!({ "@type": "error", "code": 500, "message": "cannot apply external message" }),
+        );
+        let client = client(transport);
+        assert!(matches!(
+            block_on(client.send_boc(&[1])),
+            Err(Error::TonLib { code: 500, .. })
+        ));
+    }
+
+    #[test]
+    fn new_shard_blocks_walk_back_to_known_blocks() {
+        const X: &str = "2305843009213693952";
+        const Y: &str = "6917529027641081856";
+        const Y1: &str = "5764607523034234880";
+        const Y2: &str = "8070450532247928832";
+        const W: &str = "-4611686018427387904";
+        const W1: &str = "-6917529027641081856";
+        const W2: &str = "-2305843009213693952";
+        const FULL: &str = "-9223372036854775808";
+
+        let transport = ScriptedTransport::new();
+        transport.on("blocks.lookupBlock", |request| {
+            block(request["id"]["seqno"].as_u64().unwrap() as usize)
+        });
+        transport.on("blocks.getShards", move |request| {
+            let shards = match request["id"]["seqno"].as_u64().unwrap() {
+                9 => vec![
+                    shard_block(0, X, 10),
+                    shard_block(0, Y, 20),
+                    shard_block(0, W1, 5),
+                    shard_block(0, W2, 6),
+                    shard_block(1, FULL, 30),
+                ],
+                _ => vec![
+                    // advanced by three blocks
+                    shard_block(0, X, 13),
+                    // split
+                    shard_block(0, Y1, 21),
+                    shard_block(0, Y2, 21),
+                    // merged
+                    shard_block(0, W, 7),
+                    // unchanged
+                    shard_block(1, FULL, 30),
+                    // new workchain, right after its zero state
+                    shard_block(2, FULL, 1),
+                ],
+            };
+            json!({ "@type": "blocks.shards", "shards": shards })
+        });
+        transport.on("blocks.getBlockHeader", move |request| {
+            let id = &request["id"];
+            let workchain = id["workchain"].as_i64().unwrap() as i32;
+            let shard = id["shard"].as_str().unwrap();
+            let seqno = id["seqno"].as_u64().unwrap() as usize;
+            let prev_blocks = match (shard, seqno) {
+                (Y1 | Y2, _) => vec![shard_block(0, Y, 20)],
+                (W, _) => vec![shard_block(0, W1, 5), shard_block(0, W2, 6)],
+                _ => vec![shard_block(workchain, shard, seqno - 1)],
+            };
+            json!({
+              "@type": "blocks.header",
+              "id": id,
+              "global_id": -239,
+              "version": 0,
+              "after_merge": shard == W,
+              "after_split": shard == Y1 || shard == Y2,
+              "before_split": false,
+              "want_merge": false,
+              "want_split": false,
+              "is_key_block": false,
+              "min_ref_mc_seqno": 0,
+              "prev_key_block_seqno": 0,
+              "start_lt": "0",
+              "end_lt": "0",
+              "gen_utime": 0,
+              "prev_blocks": prev_blocks
+            })
+        });
+        let client = client(transport);
+
+        let blocks = block_on(client.get_new_shard_blocks(10)).unwrap();
+        let blocks: Vec<(i32, &str, usize)> = blocks
+            .iter()
+            .map(|block| (block.workchain, block.shard.as_str(), block.seqno))
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                (0, X, 11),
+                (0, X, 12),
+                (0, X, 13),
+                (0, Y1, 21),
+                (0, Y2, 21),
+                (0, W, 7),
+                (2, FULL, 1),
+            ]
+        );
+
+        // known blocks and zero states aren't looked up
+        let headers: Vec<(i64, u64)> = sent_of(&client, "blocks.getBlockHeader")
+            .iter()
+            .map(|request| {
+                (
+                    request["id"]["workchain"].as_i64().unwrap(),
+                    request["id"]["seqno"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(headers.len(), 7);
+        assert!(!headers.contains(&(1, 30)));
+        assert!(!headers.contains(&(2, 0)));
+    }
+
+    fn dns_entry(name: &str, entry: serde_json::Value) -> serde_json::Value {
+        return json!({
+          "@type": "dns.entry",
+          "name": name,
+          "category": "6NRAUIc9uoZap8Fwq0zOZNkIOaNNz9bPcdFOAgVEOxs=",
+          "entry": entry
+        });
+    }
+
+    fn next_resolver(name: &str, resolver: &str) -> serde_json::Value {
+        return dns_entry(
+            name,
+            json!({
+              "@type": "dns.entryDataNextResolver",
+              "resolver": { "@type": "accountAddress", "account_address": resolver }
+            }),
+        );
+    }
+
+    fn wallet(name: &str) -> serde_json::Value {
+        return dns_entry(
+            name,
+            json!({
+              "@type": "dns.entryDataSmcAddress",
+              "smc_address": { "@type": "accountAddress", "account_address": ACCOUNT }
+            }),
+        );
+    }
+
+    /// Answers `dns.resolve` from the entries of each resolver, `None` being the root.
+    fn resolvers(resolvers: Vec<(Option<&'static str>, serde_json::Value)>) -> ScriptedTransport {
+        let transport = ScriptedTransport::new();
+        transport.on("dns.resolve", move |request| {
+            let resolver = request["account_address"]["account_address"].as_str();
+            let entries = resolvers
+                .iter()
+                .find(|(address, _)| *address == resolver)
+                .map(|(_, entries)| entries.clone())
+                .unwrap();
+            return json!({ "@type": "dns.resolved", "entries": entries });
+        });
+        return transport;
+    }
+
+    fn resolve_requests(client: &Client<ScriptedTransport>) -> Vec<(serde_json::Value, String)> {
+        return sent_of(client, "dns.resolve")
+            .into_iter()
+            .map(|request| {
+                let resolver = request["account_address"]["account_address"].clone();
+                (resolver, request["name"].as_str().unwrap().to_string())
+            })
+            .collect();
+    }
+
+    #[test]
+    fn resolves_dns_at_next_resolver() {
+        let client = client(resolvers(vec![
+            (None, json!([next_resolver("ton", "EQton")])),
+            (Some("EQton"), json!([wallet("foundation")])),
+        ]));
+
+        let entries =
+            block_on(client.resolve_dns("foundation.ton", types::DnsCategory::Wallet, 3)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(
+            &entries[0].entry,
+            types::DnsEntryData::SmcAddress { smc_address } if smc_address.account_address == ACCOUNT
+        ));
+        assert_eq!(
+            resolve_requests(&client),
+            [
+                (json!(null), "foundation.ton".to_string()),
+                (json!("EQton"), "foundation".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_dns_through_resolver_chain() {
+        let client = client(resolvers(vec![
+            (None, json!([next_resolver("ton", "EQton")])),
+            (
+                Some("EQton"),
+                json!([next_resolver("foundation", "EQfoundation")]),
+            ),
+            (Some("EQfoundation"), json!([wallet("wallet")])),
+        ]));
+
+        let entries =
+            block_on(client.resolve_dns("wallet.foundation.ton", types::DnsCategory::Wallet, 3))
+                .unwrap();
+        assert_eq!(entries[0].name, "wallet");
+        assert_eq!(
+            resolve_requests(&client),
+            [
+                (json!(null), "wallet.foundation.ton".to_string()),
+                (json!("EQton"), "wallet.foundation".to_string()),
+                (json!("EQfoundation"), "wallet".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_resolving_dns_when_ttl_runs_out() {
+        let chained = client(resolvers(vec![
+            (None, json!([next_resolver("ton", "EQton")])),
+            (
+                Some("EQton"),
+                json!([next_resolver("foundation", "EQfoundation")]),
+            ),
+        ]));
+
+        let entries =
+            block_on(chained.resolve_dns("wallet.foundation.ton", types::DnsCategory::Wallet, 1))
+                .unwrap();
+        assert!(matches!(
+            &entries[0].entry,
+            types::DnsEntryData::NextResolver { resolver } if resolver.account_address == "EQfoundation"
+        ));
+        assert_eq!(resolve_requests(&chained).len(), 2);
+
+        let root_only = client(resolvers(vec![(
+            None,
+            json!([next_resolver("ton", "EQton")]),
+        )]));
+        let entries =
+            block_on(root_only.resolve_dns("foundation.ton", types::DnsCategory::Wallet, 0))
+                .unwrap();
+        assert_eq!(entries[0].name, "ton");
+        assert_eq!(resolve_requests(&root_only).len(), 1);
+    }
+
+    #[test]
+    fn returns_next_resolver_when_asked_for_it() {
+        let client = client(resolvers(vec![(
+            None,
+            json!([next_resolver("ton", "EQton")]),
+        )]));
+
+        let entries =
+            block_on(client.resolve_dns("foundation.ton", types::DnsCategory::NextResolver, 3))
+                .unwrap();
+        assert!(matches!(
+            &entries[0].entry,
+            types::DnsEntryData::NextResolver { .. }
+        ));
+        assert_eq!(resolve_requests(&client).len(), 1);
+    }
+
+    #[test]
+    fn rejects_next_resolver_off_a_label_boundary() {
+        let client = client(resolvers(vec![(
+            None,
+            json!([next_resolver("on", "EQton")]),
+        )]));
+        assert!(matches!(
+            block_on(client.resolve_dns("foundation.ton", types::DnsCategory::Wallet, 3)),
+            Err(Error::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn strips_resolved_suffix() {
+        assert_eq!(
+            unresolved_part("wallet.foundation.ton", "ton").unwrap(),
+            Some("wallet.foundation".to_string())
+        );
+        assert_eq!(
+            unresolved_part("wallet.foundation.ton", ".ton").unwrap(),
+            Some("wallet.foundation".to_string())
+        );
+        assert_eq!(
+            unresolved_part("foundation.ton", "foundation.ton").unwrap(),
+            None
+        );
+        assert!(unresolved_part("foundation.ton", "ton.ton").is_err());
+        assert!(unresolved_part("foundation.ton", "n.ton").is_err());
+        assert!(unresolved_part("foundation.ton", "").is_err());
+    }
+
+    fn wallet_transport() -> ScriptedTransport {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "getAccountAddress",
+            json!({ "@type": "accountAddress", "account_address": "EQwallet" }),
+        );
+        transport.reply(
+            "createQuery",
+            json!({
+              "@type": "query.info",
+              "id": 7,
+              "valid_until": 1700000000,
+              "body_hash": "aGFzaA==",
+              "body": "te6ccgE=",
+              "init_state": ""
+            }),
+        );
+        transport.reply("query.forget", json!({ "@type": "ok" }));
+        return transport;
+    }
+
+    fn wallet_key() -> (types::InputKey, types::InitialAccountState) {
+        let key = types::Key {
+            public_key: "PuZ".to_string(),
+            secret: types::SecureBytes::from("secret"),
+        };
+        let wallet = types::InitialAccountState::wallet_v3(&key);
+        return (types::InputKey::new(key, "pass".into()), wallet);
+    }
+
+    #[test]
+    fn transfer_sends_and_forgets_query() {
+        let transport = wallet_transport();
+        transport.reply("query.send", json!({ "@type": "ok" }));
+        let client = client(transport);
+        let (input_key, wallet) = wallet_key();
+
+        let info = block_on(client.transfer(
+            &input_key,
+            &wallet,
+            vec![types::Message::new("EQdest", 1)],
+            true,
+        ))
+        .unwrap();
+        assert_eq!(info.id, 7);
+        let created = &sent_of(&client, "createQuery")[0];
+        assert_eq!(created["address"]["account_address"], "EQwallet");
+        assert_eq!(created["action"]["allow_send_to_uninited"], true);
+        assert_eq!(sent_of(&client, "query.send")[0]["id"], 7);
+        assert_eq!(sent_of(&client, "query.forget")[0]["id"], 7);
+    }
+
+    #[test]
+    fn transfer_forgets_query_when_send_fails() {
+        let transport = wallet_transport();
+        transport.reply(
+            "query.send",
+            json!({ "@type": "error", "code": 500, "message": "cannot apply external message" }),
+        );
+        let client = client(transport);
+        let (input_key, wallet) = wallet_key();
+
+        assert!(matches!(
+            block_on(client.transfer(
+                &input_key,
+                &wallet,
+                vec![types::Message::new("EQdest", 1)],
+                false,
+            )),
+            Err(Error::TonLib { code: 500, .. })
+        ));
+        assert_eq!(sent_of(&client, "query.forget")[0]["id"], 7);
+    }
+
+    #[test]
+    fn probe_clients_keep_log_settings() {
+        let config = ClientBuilder::new()
+            .lite_server_config(r#"{"liteservers": [{"ip": 0, "port": 1}]}"#)
+            .tonlib_log(TonLibLog::Discard)
+            .log_tag_level("adnl", 0)
+            .build_config()
+            .unwrap();
+        let log_requests = |client: &Client<ScriptedTransport>| {
+            [
+                "setLogStream",
+                "setLogVerbosityLevel",
+                "setLogTagVerbosityLevel",
+            ]
+            .map(|request_type| sent_of(client, request_type).len())
         };
+
+        let client = Client::with_transport(&config, ScriptedTransport::new()).unwrap();
+        assert_eq!(log_requests(&client), [1, 1, 1]);
+
+        let probe = Client::with_transport_keeping_log(&config, ScriptedTransport::new()).unwrap();
+        assert_eq!(log_requests(&probe), [0, 0, 0]);
+        assert_eq!(sent_of(&probe, "init").len(), 1);
     }
 }
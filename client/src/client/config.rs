@@ -1,7 +1,174 @@
+use std::time::Duration;
+
+use crate::{request::KeyStoreType, Error, Result};
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub lite_server_config: String,
-    pub keystore_dir: String,
-    pub request_timeout: std::time::Duration,
+    pub keystore: KeyStoreType,
+    pub request_timeout: Duration,
     pub log_level: i8,
+    pub tonlib_log: TonLibLog,
+    /// Verbosity overrides for tonlib log tags, applied after `log_level`.
+    pub log_tag_levels: Vec<(String, i32)>,
+    pub init_retry: RetryPolicy,
+    pub blockchain_name: String,
+    pub ignore_cache: bool,
+    /// Route lite-server traffic through `updateSendLiteServerQuery` updates instead
+    /// of letting tonlib open connections itself.
+    pub use_callbacks_for_network: bool,
+}
+
+/// Where tonlib writes its own log lines.
+#[derive(Debug, Clone)]
+pub enum TonLibLog {
+    /// Leave tonlib's stream alone, it writes to stderr.
+    Stderr,
+    /// Drop tonlib logs.
+    Discard,
+    /// Write to `path`, tonlib rotates the file once it exceeds `max_file_size` bytes.
+    File { path: String, max_file_size: i64 },
+    /// Forward to the `log` crate with the `tonlib` target.
+    Forward,
+}
+
+/// How `Client` construction retries a failed tonlib `init`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of `init` attempts, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub backoff_multiplier: f64,
+    /// Give up once this much time has passed since the first attempt.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            backoff_multiplier: 2.0,
+            deadline: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Fails on the first error.
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Rejects policies `next_backoff` can't work with.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            return Err(Error::InvalidInput(
+                "init retry needs at least one attempt".to_string(),
+            ));
+        }
+        if !self.backoff_multiplier.is_finite() || self.backoff_multiplier < 1.0 {
+            return Err(Error::InvalidInput(format!(
+                "init retry backoff multiplier {} is not a finite number >= 1",
+                self.backoff_multiplier
+            )));
+        }
+        if self.initial_backoff > self.max_backoff {
+            return Err(Error::InvalidInput(
+                "init retry initial backoff exceeds max backoff".to_string(),
+            ));
+        }
+        return Ok(());
+    }
+
+    /// Returns the backoff following `backoff`, at most `max_backoff`.
+    pub(crate) fn next_backoff(&self, backoff: Duration) -> Duration {
+        // `mul_f64` panics on overflow
+        return Duration::try_from_secs_f64(backoff.as_secs_f64() * self.backoff_multiplier)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+    }
+}
+
+/// Parses a lite-server config, returning it with its non-empty `liteservers` list.
+pub(crate) fn parse_lite_servers(
+    config: &str,
+) -> Result<(serde_json::Value, Vec<serde_json::Value>)> {
+    let config: serde_json::Value = serde_json::from_str(config)
+        .map_err(|e| Error::InvalidInput(format!("lite server config is not json: {e}")))?;
+    let liteservers = match config["liteservers"].as_array() {
+        Some(liteservers) if !liteservers.is_empty() => liteservers.clone(),
+        _ => {
+            return Err(Error::InvalidInput(
+                "lite server config has no liteservers".to_string(),
+            ))
+        }
+    };
+    return Ok((config, liteservers));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(initial: u64, max: Duration, multiplier: f64) -> RetryPolicy {
+        return RetryPolicy {
+            initial_backoff: Duration::from_secs(initial),
+            max_backoff: max,
+            backoff_multiplier: multiplier,
+            ..RetryPolicy::default()
+        };
+    }
+
+    #[test]
+    fn backoff_grows_up_to_max() {
+        let policy = policy(1, Duration::from_secs(10), 2.0);
+        let mut backoff = policy.initial_backoff;
+        let mut sequence = Vec::new();
+        for _ in 0..6 {
+            sequence.push(backoff.as_secs());
+            backoff = policy.next_backoff(backoff);
+        }
+        assert_eq!(sequence, [1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn backoff_clamps_instead_of_overflowing() {
+        let policy = policy(1, Duration::MAX, 1000.0);
+        assert!(policy.validate().is_ok());
+        let mut backoff = policy.initial_backoff;
+        for _ in 0..10 {
+            backoff = policy.next_backoff(backoff);
+        }
+        assert_eq!(backoff, Duration::MAX);
+        assert_eq!(policy.next_backoff(Duration::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn validate_rejects_unusable_policies() {
+        assert!(RetryPolicy::default().validate().is_ok());
+        assert!(RetryPolicy::no_retry().validate().is_ok());
+
+        let invalid = [
+            RetryPolicy {
+                max_attempts: 0,
+                ..RetryPolicy::default()
+            },
+            policy(1, Duration::from_secs(10), 0.5),
+            policy(1, Duration::from_secs(10), f64::NAN),
+            policy(1, Duration::from_secs(10), f64::INFINITY),
+            policy(20, Duration::from_secs(10), 2.0),
+        ];
+        for policy in invalid {
+            assert!(
+                matches!(policy.validate(), Err(Error::InvalidInput(_))),
+                "{policy:?}"
+            );
+        }
+    }
 }
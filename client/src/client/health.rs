@@ -0,0 +1,252 @@
+use std::{
+    net::Ipv4Addr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::{request::KeyStoreType, types, Error, Result};
+
+use super::{config::parse_lite_servers, Client, Config, RawClient, RetryPolicy, TonLib};
+
+/// How one lite server of a config answered the health check.
+#[derive(Debug, Clone)]
+pub struct LiteServerHealth {
+    /// Position in the config's `liteservers`.
+    pub index: usize,
+    /// `ip:port` of the server.
+    pub address: String,
+    /// Last masterchain block seqno known to the server.
+    pub last_seqno: Option<usize>,
+    pub info: Option<types::LiteServerInfo>,
+    /// Time taken to answer `getMasterchainInfo`.
+    pub latency: Option<Duration>,
+    /// Masterchain blocks behind the most up to date server.
+    pub lag: Option<usize>,
+    /// Why the server couldn't be checked.
+    pub error: Option<String>,
+}
+
+impl LiteServerHealth {
+    pub fn is_reachable(&self) -> bool {
+        self.last_seqno.is_some()
+    }
+}
+
+/// Queries every lite server of `config.lite_server_config` through its own tonlib
+/// session and compares their last masterchain block.
+///
+/// Servers are checked in parallel, each within `config.request_timeout`.
+pub fn check_lite_servers(config: &Config, library: Arc<TonLib>) -> Result<Vec<LiteServerHealth>> {
+    let (lite_server_config, liteservers) = parse_lite_servers(&config.lite_server_config)?;
+
+    let checks: Vec<_> = liteservers
+        .iter()
+        .enumerate()
+        .map(|(index, liteserver)| {
+            let mut pinned = lite_server_config.clone();
+            pinned["liteservers"] = serde_json::json!([liteserver]);
+            let check_config = Config {
+                lite_server_config: pinned.to_string(),
+                keystore: KeyStoreType::InMemory,
+                init_retry: RetryPolicy::no_retry(),
+                use_callbacks_for_network: false,
+                ..config.clone()
+            };
+            let library = library.clone();
+            std::thread::Builder::new()
+                .name(format!("lite_server_check_{index}"))
+                .spawn(move || check_lite_server(&check_config, library))
+        })
+        .collect::<std::io::Result<_>>()?;
+
+    let mut health: Vec<LiteServerHealth> = checks
+        .into_iter()
+        .zip(liteservers.iter())
+        .enumerate()
+        .map(|(index, (check, liteserver))| {
+            let mut health = LiteServerHealth {
+                index,
+                address: lite_server_address(liteserver),
+                last_seqno: None,
+                info: None,
+                latency: None,
+                lag: None,
+                error: None,
+            };
+            match check.join() {
+                Ok(Ok((last_seqno, info, latency))) => {
+                    health.last_seqno = Some(last_seqno);
+                    health.info = info;
+                    health.latency = Some(latency);
+                }
+                Ok(Err(err)) => health.error = Some(err.to_string()),
+                Err(_) => health.error = Some("health check panicked".to_string()),
+            }
+            health
+        })
+        .collect();
+
+    let best_seqno = health.iter().filter_map(|health| health.last_seqno).max();
+    for health in &mut health {
+        health.lag = health
+            .last_seqno
+            .zip(best_seqno)
+            .map(|(last_seqno, best_seqno)| best_seqno - last_seqno);
+    }
+    return Ok(health);
+}
+
+/// Returns `lite_server_config` restricted to its `count` least lagging lite
+/// servers by `health`, the fastest first among equally up to date ones.
+pub(crate) fn healthiest_lite_servers(
+    lite_server_config: &str,
+    health: Vec<LiteServerHealth>,
+    count: usize,
+) -> Result<String> {
+    let (mut lite_server_config, liteservers) = parse_lite_servers(lite_server_config)?;
+    let mut health: Vec<LiteServerHealth> = health
+        .into_iter()
+        .filter(|health| match &health.error {
+            Some(err) => {
+                warn!("lite server {} is unhealthy: {err}", health.address);
+                false
+            }
+            None => true,
+        })
+        .collect();
+    if health.is_empty() {
+        return Err(Error::Unavailable(
+            "no lite server answered the health check".to_string(),
+        ));
+    }
+
+    health.sort_by_key(|health| (health.lag, health.latency));
+    health.truncate(count);
+    for health in &health {
+        info!(
+            "using lite server {}, {} blocks behind, answered in {:?}",
+            health.address,
+            health.lag.unwrap_or_default(),
+            health.latency.unwrap_or_default()
+        );
+    }
+
+    let selected: Vec<serde_json::Value> = health
+        .iter()
+        .map(|health| liteservers[health.index].clone())
+        .collect();
+    lite_server_config["liteservers"] = serde_json::json!(selected);
+    return Ok(lite_server_config.to_string());
+}
+
+fn check_lite_server(
+    config: &Config,
+    library: Arc<TonLib>,
+) -> Result<(usize, Option<types::LiteServerInfo>, Duration)> {
+    // tonlib's log stream is process-wide, the caller's client sets it up
+    let client = Client::with_transport_keeping_log(
+        config,
+        RawClient::with_library(library, config.log_level),
+    )?;
+
+    let started = Instant::now();
+    let master_chain_info = futures::executor::block_on(client.get_master_chain_info())?;
+    let latency = started.elapsed();
+
+    // older lite servers don't implement getInfo
+    let info = match futures::executor::block_on(client.get_lite_server_info()) {
+        Ok(info) => Some(info),
+        Err(err) => {
+            warn!("lite server info error: {err}");
+            None
+        }
+    };
+    return Ok((master_chain_info.last.seqno, info, latency));
+}
+
+/// The config stores the ipv4 address as a signed 32 bit integer.
+fn lite_server_address(liteserver: &serde_json::Value) -> String {
+    let ip = Ipv4Addr::from(liteserver["ip"].as_i64().unwrap_or_default() as u32);
+    return format!("{ip}:{}", liteserver["port"]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+      "@type": "config.global",
+      "liteservers": [
+        {"ip": 1, "port": 10},
+        {"ip": 2, "port": 20},
+        {"ip": 3, "port": 30},
+        {"ip": 4, "port": 40}
+      ],
+      "validator": {"@type": "validator.config.global"}
+    }"#;
+
+    fn reachable(index: usize, lag: usize, latency_ms: u64) -> LiteServerHealth {
+        return LiteServerHealth {
+            index,
+            address: String::new(),
+            last_seqno: Some(100 - lag),
+            info: None,
+            latency: Some(Duration::from_millis(latency_ms)),
+            lag: Some(lag),
+            error: None,
+        };
+    }
+
+    fn unreachable(index: usize) -> LiteServerHealth {
+        return LiteServerHealth {
+            index,
+            address: String::new(),
+            last_seqno: None,
+            info: None,
+            latency: None,
+            lag: None,
+            error: Some("timeout".to_string()),
+        };
+    }
+
+    fn ports(lite_server_config: &str) -> Vec<u64> {
+        let (_, liteservers) = parse_lite_servers(lite_server_config).unwrap();
+        return liteservers
+            .iter()
+            .map(|liteserver| liteserver["port"].as_u64().unwrap())
+            .collect();
+    }
+
+    #[test]
+    fn orders_by_lag_then_latency() {
+        let health = vec![
+            reachable(0, 1, 5),
+            reachable(1, 0, 30),
+            unreachable(2),
+            reachable(3, 0, 10),
+        ];
+
+        let selected = healthiest_lite_servers(CONFIG, health.clone(), 2).unwrap();
+        assert_eq!(ports(&selected), [40, 20]);
+
+        let selected = healthiest_lite_servers(CONFIG, health, 10).unwrap();
+        assert_eq!(ports(&selected), [40, 20, 10]);
+        let selected: serde_json::Value = serde_json::from_str(&selected).unwrap();
+        assert_eq!(selected["@type"], "config.global");
+        assert_eq!(selected["validator"]["@type"], "validator.config.global");
+    }
+
+    #[test]
+    fn fails_without_reachable_lite_server() {
+        let result = healthiest_lite_servers(CONFIG, vec![unreachable(0), unreachable(1)], 1);
+        assert!(matches!(result, Err(Error::Unavailable(_))));
+    }
+
+    #[test]
+    fn formats_lite_server_address() {
+        let liteserver = serde_json::json!({ "ip": -1062731775, "port": 4924 });
+        assert_eq!(lite_server_address(&liteserver), "192.168.0.1:4924");
+    }
+}
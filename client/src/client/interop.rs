@@ -1,45 +1,378 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
 use lazy_static::lazy_static;
 
+use crate::{Error, Result};
+
 const OUT_DIR: &str = env!("OUT_DIR");
 
-lazy_static! {
-    // tonlib
-    static ref TONLIB: libloading::Library = unsafe {
-        let lib_path = std::env::var("TON_LIB_PATH").unwrap_or(format!(
-            "{OUT_DIR}/distlib/{}/libtonlibjson.{}.so",
-            std::env::consts::OS,
-            std::env::consts::ARCH
-        ));
-        libloading::Library::new(lib_path).unwrap()
-    };
+pub type Handle = *mut std::ffi::c_void;
+
+const SYSTEM_DIRS: &[&str] = &["/usr/local/lib", "/usr/lib", "/opt/homebrew/lib"];
+
+/// Where `TonLib::load` looks for the tonlibjson shared library.
+///
+/// Candidates are tried in order: `TON_LIB_PATH`, `paths`, the `distlib` directory
+/// bundled at build time, then the system library directories. A candidate may be
+/// a library file or a directory containing one.
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub paths: Vec<PathBuf>,
+    pub use_env: bool,
+    pub use_bundled: bool,
+    pub use_system: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            use_env: true,
+            use_bundled: true,
+            use_system: true,
+        }
+    }
+}
+
+impl LoadOptions {
+    /// Only tries the given paths.
+    pub fn with_paths(paths: Vec<PathBuf>) -> Self {
+        Self {
+            paths,
+            use_env: false,
+            use_bundled: false,
+            use_system: false,
+        }
+    }
+
+    fn candidates(&self) -> Vec<PathBuf> {
+        return self.candidates_in(
+            std::env::var_os("TON_LIB_PATH").map(PathBuf::from),
+            &Path::new(OUT_DIR).join("distlib"),
+            SYSTEM_DIRS,
+        );
+    }
+
+    /// `candidates` with the environment and the well-known directories passed in.
+    fn candidates_in(
+        &self,
+        env_path: Option<PathBuf>,
+        distlib: &Path,
+        system_dirs: &[&str],
+    ) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if self.use_env {
+            paths.extend(env_path);
+        }
+        paths.extend(self.paths.iter().cloned());
+
+        let mut dirs = Vec::new();
+        if self.use_bundled {
+            for os in os_names() {
+                dirs.push(distlib.join(os));
+            }
+        }
+        if self.use_system {
+            dirs.extend(system_dirs.iter().map(PathBuf::from));
+        }
+        // well-known dirs are only searched when present
+        paths.extend(dirs.into_iter().filter(|dir| dir.is_dir()));
+
+        let mut candidates = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                candidates.extend(library_names().into_iter().map(|name| path.join(name)));
+            } else {
+                candidates.push(path);
+            }
+        }
+        if self.use_system {
+            // bare names go through the dynamic loader search path
+            candidates.extend(library_names().into_iter().map(PathBuf::from));
+        }
+        candidates.dedup();
+        return candidates;
+    }
+}
+
+pub struct TonLib {
+    path: Option<PathBuf>,
+    _library: Option<libloading::Library>,
     // tonlib_client_set_verbosity_level
-    pub static ref TONLIB_CLIENT_SET_VERBOSITY_LEVEL: libloading::Symbol<'static, unsafe extern "C" fn(log_level: i32)> =
-        unsafe { TONLIB.get(b"tonlib_client_set_verbosity_level").unwrap() };
+    pub(crate) set_verbosity_level: unsafe extern "C" fn(log_level: i32),
     // tonlib_client_json_create
-    pub static ref TONLIB_CLIENT_JSON_CREATE: libloading::Symbol<'static, unsafe extern "C" fn() -> *mut std::ffi::c_void> =
-        unsafe { TONLIB.get(b"tonlib_client_json_create").unwrap() };
+    pub(crate) json_create: unsafe extern "C" fn() -> Handle,
     // tonlib_client_json_send
-    pub static ref TONLIB_CLIENT_JSON_SEND: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(client: *mut std::ffi::c_void, request: *const std::ffi::c_char),
-    > = unsafe { TONLIB.get(b"tonlib_client_json_send").unwrap() };
+    pub(crate) json_send: unsafe extern "C" fn(client: Handle, request: *const std::ffi::c_char),
     // tonlib_client_json_receive
-    pub static ref TONLIB_CLIENT_JSON_RECEIVE: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(
-            client: *mut std::ffi::c_void,
-            timeout: std::ffi::c_double,
-        ) -> *const std::ffi::c_char,
-    > = unsafe { TONLIB.get(b"tonlib_client_json_receive").unwrap() };
+    pub(crate) json_receive: unsafe extern "C" fn(
+        client: Handle,
+        timeout: std::ffi::c_double,
+    ) -> *const std::ffi::c_char,
     // tonlib_client_json_execute
-    pub static ref TONLIB_CLIENT_JSON_EXECUTE: libloading::Symbol<
-        'static,
-        unsafe extern "C" fn(
-            client: *mut std::ffi::c_void,
-            request: *const std::ffi::c_char,
-        ) -> *const std::ffi::c_char,
-    > = unsafe { TONLIB.get(b"tonlib_client_json_execute").unwrap() };
+    pub(crate) json_execute: unsafe extern "C" fn(
+        client: Handle,
+        request: *const std::ffi::c_char,
+    ) -> *const std::ffi::c_char,
     // tonlib_client_json_destroy
-    pub static ref TONLIB_CLIENT_JSON_DESTROY: libloading::Symbol<'static, unsafe extern "C" fn(client: *mut std::ffi::c_void)> =
-        unsafe { TONLIB.get(b"tonlib_client_json_destroy").unwrap() };
+    pub(crate) json_destroy: unsafe extern "C" fn(client: Handle),
+}
+
+impl std::fmt::Debug for TonLib {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TonLib").field("path", &self.path).finish()
+    }
+}
+
+impl TonLib {
+    /// Loads the first tonlibjson library found by `options` that exports every
+    /// symbol the client needs.
+    ///
+    /// The error lists each path tried and why it was rejected.
+    pub fn load(options: &LoadOptions) -> Result<Arc<Self>> {
+        let mut tried = Vec::new();
+        for candidate in options.candidates() {
+            if candidate.is_absolute() && !candidate.exists() {
+                tried.push(format!("  {}: not found", candidate.display()));
+                continue;
+            }
+            match unsafe { Self::load_path(&candidate) } {
+                Ok(tonlib) => return Ok(Arc::new(tonlib)),
+                Err(err) => tried.push(format!("  {}: {err}", candidate.display())),
+            }
+        }
+        return Err(Error::LibraryLoad(format!(
+            "tonlibjson not found, tried:\n{}",
+            tried.join("\n")
+        )));
+    }
+
+    /// Returns the process-wide library: the one linked at build time with the
+    /// `link-static` or `link-dynamic` features, otherwise the one found with the
+    /// default `LoadOptions`.
+    ///
+    /// Only a successful load is kept, a failed one is retried on the next call.
+    pub fn global() -> Result<Arc<Self>> {
+        return load_cached(&TONLIB, Self::load_global);
+    }
+
+    /// Path the library was loaded from, `None` when it's linked at build time.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    #[cfg(not(any(feature = "link-static", feature = "link-dynamic")))]
+    fn load_global() -> Result<Arc<Self>> {
+        Self::load(&LoadOptions::default())
+    }
+
+    #[cfg(any(feature = "link-static", feature = "link-dynamic"))]
+    fn load_global() -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            path: None,
+            _library: None,
+            set_verbosity_level: linked::tonlib_client_set_verbosity_level,
+            json_create: linked::tonlib_client_json_create,
+            json_send: linked::tonlib_client_json_send,
+            json_receive: linked::tonlib_client_json_receive,
+            json_execute: linked::tonlib_client_json_execute,
+            json_destroy: linked::tonlib_client_json_destroy,
+        }))
+    }
+
+    unsafe fn load_path(path: &Path) -> std::result::Result<Self, String> {
+        let library = libloading::Library::new(path).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            set_verbosity_level: symbol(&library, "tonlib_client_set_verbosity_level")?,
+            json_create: symbol(&library, "tonlib_client_json_create")?,
+            json_send: symbol(&library, "tonlib_client_json_send")?,
+            json_receive: symbol(&library, "tonlib_client_json_receive")?,
+            json_execute: symbol(&library, "tonlib_client_json_execute")?,
+            json_destroy: symbol(&library, "tonlib_client_json_destroy")?,
+            path: Some(path.to_path_buf()),
+            _library: Some(library),
+        })
+    }
+}
+
+unsafe fn symbol<T: Copy>(
+    library: &libloading::Library,
+    name: &str,
+) -> std::result::Result<T, String> {
+    library
+        .get::<T>(name.as_bytes())
+        .map(|symbol| *symbol)
+        .map_err(|e| format!("missing symbol {name}: {e}"))
+}
+
+/// Returns the value in `cache`, or stores the one `load` returns if it succeeds.
+fn load_cached<T>(
+    cache: &Mutex<Option<Arc<T>>>,
+    load: impl FnOnce() -> Result<Arc<T>>,
+) -> Result<Arc<T>> {
+    let mut cache = cache.lock().unwrap();
+    if let Some(cached) = cache.as_ref() {
+        return Ok(cached.clone());
+    }
+    let loaded = load()?;
+    *cache = Some(loaded.clone());
+    return Ok(loaded);
+}
+
+/// Directory names of the current OS inside `distlib`.
+fn os_names() -> Vec<&'static str> {
+    match std::env::consts::OS {
+        "macos" => vec!["darwin", "macos"],
+        os => vec![os],
+    }
+}
+
+/// File names the library may have on the current platform, arch suffixed ones first.
+fn library_names() -> Vec<String> {
+    let (prefix, extension) = match std::env::consts::OS {
+        "macos" => ("lib", "dylib"),
+        "windows" => ("", "dll"),
+        _ => ("lib", "so"),
+    };
+    let arches: &[&str] = match std::env::consts::ARCH {
+        "aarch64" => &["aarch64", "arm64"],
+        "x86_64" => &["x86_64", "amd64"],
+        arch => &[arch],
+    };
+
+    let mut names: Vec<String> = arches
+        .iter()
+        .map(|arch| format!("{prefix}tonlibjson.{arch}.{extension}"))
+        .collect();
+    names.push(format!("{prefix}tonlibjson.{extension}"));
+    return names;
+}
+
+#[cfg(any(feature = "link-static", feature = "link-dynamic"))]
+mod linked {
+    use super::Handle;
+
+    extern "C" {
+        pub fn tonlib_client_set_verbosity_level(log_level: i32);
+        pub fn tonlib_client_json_create() -> Handle;
+        pub fn tonlib_client_json_send(client: Handle, request: *const std::ffi::c_char);
+        pub fn tonlib_client_json_receive(
+            client: Handle,
+            timeout: std::ffi::c_double,
+        ) -> *const std::ffi::c_char;
+        pub fn tonlib_client_json_execute(
+            client: Handle,
+            request: *const std::ffi::c_char,
+        ) -> *const std::ffi::c_char;
+        pub fn tonlib_client_json_destroy(client: Handle);
+    }
+}
+
+lazy_static! {
+    static ref TONLIB: Mutex<Option<Arc<TonLib>>> = Mutex::new(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tonlib-{name}-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    fn in_dir(dir: &Path) -> Vec<PathBuf> {
+        return library_names()
+            .into_iter()
+            .map(|name| dir.join(name))
+            .collect();
+    }
+
+    #[test]
+    fn candidates_follow_search_order() {
+        let root = temp_dir("candidates");
+        let distlib = root.join("distlib");
+        let bundled = distlib.join(os_names()[0]);
+        let user_dir = root.join("user");
+        let system_dir = root.join("system");
+        for dir in [&bundled, &user_dir, &system_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let missing_system_dir = root.join("missing");
+
+        let options = LoadOptions {
+            paths: vec![PathBuf::from("/opt/libtonlibjson.so"), user_dir.clone()],
+            ..LoadOptions::default()
+        };
+        let candidates = options.candidates_in(
+            Some(PathBuf::from("/env/libtonlibjson.so")),
+            &distlib,
+            &[
+                system_dir.to_str().unwrap(),
+                missing_system_dir.to_str().unwrap(),
+            ],
+        );
+
+        let mut expected = vec![
+            PathBuf::from("/env/libtonlibjson.so"),
+            PathBuf::from("/opt/libtonlibjson.so"),
+        ];
+        expected.extend(in_dir(&user_dir));
+        expected.extend(in_dir(&bundled));
+        expected.extend(in_dir(&system_dir));
+        expected.extend(library_names().into_iter().map(PathBuf::from));
+        assert_eq!(candidates, expected);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn with_paths_only_tries_given_paths() {
+        let options = LoadOptions::with_paths(vec![PathBuf::from("/opt/libtonlibjson.so")]);
+        let candidates = options.candidates_in(
+            Some(PathBuf::from("/env/libtonlibjson.so")),
+            Path::new("/"),
+            &["/"],
+        );
+        assert_eq!(candidates, [PathBuf::from("/opt/libtonlibjson.so")]);
+    }
+
+    #[test]
+    fn load_error_lists_paths_tried() {
+        let root = temp_dir("load");
+        let not_a_library = root.join("libtonlibjson.so");
+        std::fs::write(&not_a_library, "not a library").unwrap();
+        let missing = root.join("missing.so");
+
+        let options = LoadOptions::with_paths(vec![missing.clone(), not_a_library.clone()]);
+        let message = match TonLib::load(&options) {
+            Err(Error::LibraryLoad(message)) => message,
+            result => panic!("unexpected {result:?}"),
+        };
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines[0], "tonlibjson not found, tried:");
+        assert_eq!(lines[1], format!("  {}: not found", missing.display()));
+        assert!(lines[2].starts_with(&format!("  {}: ", not_a_library.display())));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn only_successful_load_is_cached() {
+        let cache = Mutex::new(None);
+        let failed = load_cached(&cache, || {
+            Err::<Arc<u32>, _>(Error::LibraryLoad("missing".to_string()))
+        });
+        assert!(failed.is_err());
+        assert!(cache.lock().unwrap().is_none());
+
+        assert_eq!(*load_cached(&cache, || Ok(Arc::new(1))).unwrap(), 1);
+        assert_eq!(*load_cached(&cache, || Ok(Arc::new(2))).unwrap(), 1);
+    }
 }
@@ -1,9 +1,24 @@
+mod builder;
+mod cassette;
 mod client;
 mod config;
+mod health;
 mod interop;
+mod pool;
 mod raw_client;
 mod raw_receiver;
+mod scripted;
+mod tonlib_log;
+mod transport;
 
+pub use builder::{ClientBuilder, MAINNET_CONFIG, TESTNET_CONFIG};
+pub use cassette::{CassetteEntry, RecordingTransport, ReplayTransport};
 pub use client::Client;
-pub use config::Config;
+pub use config::{Config, RetryPolicy, TonLibLog};
+pub use health::{check_lite_servers, LiteServerHealth};
+pub use interop::{LoadOptions, TonLib};
+pub use pool::{Balancing, ClientPool, PoolOptions, PoolTransport};
 pub use raw_client::RawClient;
+pub use raw_receiver::{CancelHandle, ResponseFuture, Updates};
+pub use scripted::ScriptedTransport;
+pub use transport::Transport;
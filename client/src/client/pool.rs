@@ -0,0 +1,998 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crossbeam::channel;
+use log::{debug, error, info, warn};
+
+use crate::{Error, Result};
+
+use super::{config::parse_lite_servers, Client, Config, RawClient, TonLib, Transport};
+
+/// A `Client` spreading requests over several tonlib sessions.
+pub type ClientPool<T = RawClient> = Client<PoolTransport<T>>;
+
+/// How `PoolTransport` picks a session for the next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Balancing {
+    RoundRobin,
+    LeastInFlight,
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    /// Number of tonlib sessions.
+    pub size: usize,
+    /// Give each session a config with a single lite server, taken in turn
+    /// from the `liteservers` list.
+    pub pin_lite_servers: bool,
+    pub balancing: Balancing,
+    /// Consecutive failures (timeouts or lite server connection errors) after which
+    /// a session stops receiving requests.
+    pub max_failures: u32,
+    /// How long a removed session waits before a probe request checks whether
+    /// it recovered.
+    pub recheck_interval: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            pin_lite_servers: true,
+            balancing: Balancing::LeastInFlight,
+            max_failures: 3,
+            recheck_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Requests sent to every session, answered once all sessions replied.
+const BROADCAST_TYPES: &[&str] = &["init", "close"];
+
+/// Requests using the keystore. Each session has its own in-memory keystore, so
+/// they all go to the first session.
+const KEYSTORE_TYPES: &[&str] = &[
+    "createNewKey",
+    "exportKey",
+    "exportPemKey",
+    "exportEncryptedKey",
+    "exportUnencryptedKey",
+    "importKey",
+    "importPemKey",
+    "importEncryptedKey",
+    "importUnencryptedKey",
+    "deleteKey",
+    "deleteAllKeys",
+    "changeLocalPassword",
+    "createQuery",
+];
+
+/// `@extra` prefix of the pool's own probe requests, followed by the session index.
+const PROBE_EXTRA: &str = "pool-probe-";
+
+/// A kind of tonlib object only valid in the session that created it.
+struct HandleKind {
+    /// Answer carrying the id of a new object.
+    created_by: &'static str,
+    /// Requests taking the id back.
+    used_by: &'static [&'static str],
+    /// Request releasing the object.
+    released_by: &'static str,
+}
+
+const HANDLE_KINDS: &[HandleKind] = &[
+    HandleKind {
+        created_by: "smc.info",
+        used_by: &[
+            "smc.runGetMethod",
+            "smc.getCode",
+            "smc.getData",
+            "smc.getState",
+            "smc.forget",
+        ],
+        released_by: "smc.forget",
+    },
+    HandleKind {
+        created_by: "query.info",
+        used_by: &[
+            "query.send",
+            "query.estimateFees",
+            "query.getInfo",
+            "query.forget",
+        ],
+        released_by: "query.forget",
+    },
+];
+
+/// A session object, known to the client under a pool-wide id.
+struct Handle {
+    kind: &'static str,
+    session: usize,
+    id: i64,
+}
+
+struct Session<T> {
+    transport: T,
+    in_flight: AtomicUsize,
+    failures: AtomicU32,
+    healthy: AtomicBool,
+    initialized: AtomicBool,
+    probing: AtomicBool,
+    /// When the session was last removed from rotation or probed.
+    checked_at: Mutex<Instant>,
+}
+
+struct Route {
+    session: usize,
+}
+
+struct Broadcast {
+    init: bool,
+    /// Sessions the request was sent to that haven't answered yet.
+    pending: BTreeSet<usize>,
+    success: Option<String>,
+    error: Option<String>,
+}
+
+/// `Transport` over several sessions, `RawClient` ones by default.
+///
+/// Each session gets its own receive thread, their messages are merged into a
+/// single stream for the `Client` receiver. Sessions failing `max_failures` times
+/// in a row are removed from rotation, and put back once a probe request sent every
+/// `recheck_interval` succeeds.
+///
+/// Ids of session objects, like contracts loaded with `smc.load`, are replaced by
+/// pool-wide ids, requests using them go to the session that created the object.
+pub struct PoolTransport<T: Transport = RawClient> {
+    sessions: Vec<Arc<Session<T>>>,
+    configs: Vec<Option<String>>,
+    options: PoolOptions,
+    request_timeout: Duration,
+    next: AtomicUsize,
+    /// Last `init` broadcast, replayed to sessions that failed it.
+    init_request: Mutex<Option<serde_json::Value>>,
+    routes: Mutex<BTreeMap<String, Route>>,
+    handles: Mutex<BTreeMap<i64, Handle>>,
+    next_handle: AtomicI64,
+    broadcasts: Mutex<BTreeMap<String, Broadcast>>,
+    messages: channel::Receiver<(usize, String)>,
+    running: Arc<AtomicBool>,
+    pumps: Vec<std::thread::JoinHandle<()>>,
+}
+
+impl Client<PoolTransport> {
+    /// Creates a client backed by `options.size` tonlib sessions of the process-wide library.
+    pub fn pool(config: &Config, options: PoolOptions) -> Result<Self> {
+        Self::with_transport(
+            config,
+            PoolTransport::new(config, options, TonLib::global()?)?,
+        )
+    }
+}
+
+impl<T: Transport> Client<PoolTransport<T>> {
+    /// Number of sessions still receiving requests.
+    pub fn healthy_sessions(&self) -> usize {
+        self.transport().healthy_sessions()
+    }
+}
+
+impl PoolTransport {
+    /// Creates `options.size` `RawClient` sessions on `library`.
+    pub fn new(config: &Config, options: PoolOptions, library: Arc<TonLib>) -> Result<Self> {
+        let sessions = (0..options.size)
+            .map(|_| RawClient::with_library(library.clone(), config.log_level))
+            .collect();
+        Self::with_sessions(config, options, sessions)
+    }
+}
+
+impl<T: Transport> PoolTransport<T> {
+    /// Balances over the given sessions, `options.size` is ignored.
+    pub fn with_sessions(
+        config: &Config,
+        options: PoolOptions,
+        transports: Vec<T>,
+    ) -> Result<Self> {
+        if transports.is_empty() {
+            return Err(Error::InvalidInput("pool has no sessions".to_string()));
+        }
+        let size = transports.len();
+
+        let configs = match options.pin_lite_servers {
+            true => pinned_configs(&config.lite_server_config, size)?,
+            false => vec![None; size],
+        };
+
+        let running = Arc::new(AtomicBool::new(true));
+        let (sender, messages) = channel::unbounded();
+        let mut sessions = Vec::with_capacity(size);
+        let mut pumps = Vec::with_capacity(size);
+        for (index, transport) in transports.into_iter().enumerate() {
+            let session = Arc::new(Session {
+                transport,
+                in_flight: AtomicUsize::new(0),
+                failures: AtomicU32::new(0),
+                healthy: AtomicBool::new(true),
+                initialized: AtomicBool::new(false),
+                probing: AtomicBool::new(false),
+                checked_at: Mutex::new(Instant::now()),
+            });
+
+            let pump_session = session.clone();
+            let pump_sender = sender.clone();
+            let pump_running = running.clone();
+            let pump = std::thread::Builder::new()
+                .name(format!("pool_session_receive_looper_{index}"))
+                .spawn(move || {
+                    while pump_running.load(Ordering::SeqCst) {
+                        if let Some(msg) = pump_session.transport.receive() {
+                            _ = pump_sender.send((index, msg));
+                        }
+                    }
+                })?;
+
+            sessions.push(session);
+            pumps.push(pump);
+        }
+
+        Ok(Self {
+            sessions,
+            configs,
+            options,
+            request_timeout: config.request_timeout,
+            next: AtomicUsize::new(0),
+            init_request: Mutex::new(None),
+            routes: Mutex::new(BTreeMap::new()),
+            handles: Mutex::new(BTreeMap::new()),
+            next_handle: AtomicI64::new(1),
+            broadcasts: Mutex::new(BTreeMap::new()),
+            messages,
+            running,
+            pumps,
+        })
+    }
+
+    pub fn healthy_sessions(&self) -> usize {
+        self.sessions
+            .iter()
+            .filter(|session| session.healthy.load(Ordering::SeqCst))
+            .count()
+    }
+
+    fn pick(&self) -> Result<usize> {
+        let healthy = (0..self.sessions.len())
+            .filter(|&index| self.sessions[index].healthy.load(Ordering::SeqCst));
+        let picked = match self.options.balancing {
+            Balancing::RoundRobin => {
+                let healthy: Vec<usize> = healthy.collect();
+                match healthy.is_empty() {
+                    true => None,
+                    false => {
+                        let next = self.next.fetch_add(1, Ordering::SeqCst);
+                        Some(healthy[next % healthy.len()])
+                    }
+                }
+            }
+            Balancing::LeastInFlight => {
+                healthy.min_by_key(|&index| self.sessions[index].in_flight.load(Ordering::SeqCst))
+            }
+        };
+        picked.ok_or_else(|| Error::Unavailable("no healthy session in pool".to_string()))
+    }
+
+    fn record_success(&self, index: usize) {
+        self.sessions[index].failures.store(0, Ordering::SeqCst);
+    }
+
+    fn record_failure(&self, index: usize, reason: &str) {
+        let session = &self.sessions[index];
+        let failures = session.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.options.max_failures && session.healthy.swap(false, Ordering::SeqCst) {
+            *session.checked_at.lock().unwrap() = Instant::now();
+            warn!("pool session {index} removed after {failures} failures, last: {reason}");
+        }
+    }
+
+    /// Probes the sessions out of rotation for `recheck_interval`: with `init` if
+    /// they failed it, otherwise with a masterchain info query.
+    fn recheck_sessions(&self) {
+        let init_request = match self.init_request.lock().unwrap().clone() {
+            Some(init_request) => init_request,
+            // not initialized yet or closed
+            None => return,
+        };
+        for (index, session) in self.sessions.iter().enumerate() {
+            if session.healthy.load(Ordering::SeqCst) {
+                continue;
+            }
+            let mut checked_at = session.checked_at.lock().unwrap();
+            // an unanswered probe is retried once it times out
+            let wait = match session.probing.load(Ordering::SeqCst) {
+                true => self.request_timeout.max(self.options.recheck_interval),
+                false => self.options.recheck_interval,
+            };
+            if checked_at.elapsed() < wait {
+                continue;
+            }
+            *checked_at = Instant::now();
+
+            let mut probe = match session.initialized.load(Ordering::SeqCst) {
+                true => serde_json::json!({ "@type": "blocks.getMasterchainInfo" }),
+                false => self.session_request(index, &init_request),
+            };
+            probe["@extra"] = serde_json::json!(format!("{PROBE_EXTRA}{index}"));
+            session.probing.store(true, Ordering::SeqCst);
+            debug!("pool session {index} probe");
+            if let Err(err) = session.transport.send(&probe.to_string()) {
+                warn!("pool session {index} probe error: {err}");
+            }
+        }
+    }
+
+    fn probed(&self, index: usize, msg: &str, is_error: bool) {
+        let session = match self.sessions.get(index) {
+            Some(session) => session,
+            None => return,
+        };
+        session.probing.store(false, Ordering::SeqCst);
+        *session.checked_at.lock().unwrap() = Instant::now();
+        if is_error {
+            warn!("pool session {index} still unhealthy: {msg}");
+            return;
+        }
+        session.initialized.store(true, Ordering::SeqCst);
+        session.failures.store(0, Ordering::SeqCst);
+        if !session.healthy.swap(true, Ordering::SeqCst) {
+            info!("pool session {index} is back in rotation");
+        }
+    }
+
+    /// Returns the session owning the object a request refers to, with the
+    /// request's id translated to the session's one.
+    fn handle_session(&self, request: &mut serde_json::Value) -> Result<Option<usize>> {
+        let request_type = request["@type"].as_str().unwrap_or_default().to_string();
+        let kind = match HANDLE_KINDS
+            .iter()
+            .find(|kind| kind.used_by.contains(&request_type.as_str()))
+        {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+
+        let id = request["id"].as_i64().unwrap_or_default();
+        let mut handles = self.handles.lock().unwrap();
+        let (session, session_id) = match handles.get(&id) {
+            Some(handle) if handle.kind == kind.created_by => (handle.session, handle.id),
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "unknown {} id {id}",
+                    kind.created_by
+                )))
+            }
+        };
+        if kind.released_by == request_type {
+            handles.remove(&id);
+        }
+        request["id"] = serde_json::json!(session_id);
+        return Ok(Some(session));
+    }
+
+    /// Replaces the session id of an object created by the session at `index` with
+    /// a pool-wide one, returns whether `answer` changed.
+    fn register_handle(&self, index: usize, answer: &mut serde_json::Value) -> bool {
+        let kind = match HANDLE_KINDS
+            .iter()
+            .find(|kind| answer["@type"] == kind.created_by)
+        {
+            Some(kind) => kind.created_by,
+            None => return false,
+        };
+        let id = match answer["id"].as_i64() {
+            Some(id) => id,
+            None => return false,
+        };
+
+        let mut handles = self.handles.lock().unwrap();
+        let known = handles
+            .iter()
+            .find(|(_, handle)| handle.kind == kind && handle.session == index && handle.id == id)
+            .map(|(handle, _)| *handle);
+        let handle = known.unwrap_or_else(|| {
+            let handle = self.next_handle.fetch_add(1, Ordering::SeqCst);
+            handles.insert(
+                handle,
+                Handle {
+                    kind,
+                    session: index,
+                    id,
+                },
+            );
+            handle
+        });
+        answer["id"] = serde_json::json!(handle);
+        return true;
+    }
+
+    /// Adapts a broadcast request to the session at `index`.
+    fn session_request(&self, index: usize, request: &serde_json::Value) -> serde_json::Value {
+        let mut request = request.clone();
+        if let Some(config) = &self.configs[index] {
+            if request["@type"] == "init" {
+                request["options"]["config"]["config"] = serde_json::json!(config);
+            }
+        }
+        return request;
+    }
+
+    /// Sends `request` to the sessions in rotation, the others get `init` through
+    /// their probe.
+    fn broadcast(&self, request: &serde_json::Value) -> Result<()> {
+        let extra = request["@extra"].as_str().unwrap_or_default().to_string();
+        *self.init_request.lock().unwrap() = match request["@type"] == "init" {
+            true => Some(request.clone()),
+            false => None,
+        };
+        let pending: BTreeSet<usize> = (0..self.sessions.len())
+            .filter(|&index| self.sessions[index].healthy.load(Ordering::SeqCst))
+            .collect();
+        if pending.is_empty() {
+            return Err(Error::Unavailable("no healthy session in pool".to_string()));
+        }
+        self.broadcasts.lock().unwrap().insert(
+            extra.clone(),
+            Broadcast {
+                init: request["@type"] == "init",
+                pending: pending.clone(),
+                success: None,
+                error: None,
+            },
+        );
+
+        for index in pending {
+            let request = self.session_request(index, request);
+            if let Err(err) = self.sessions[index].transport.send(&request.to_string()) {
+                self.broadcasts.lock().unwrap().remove(&extra);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Folds broadcast answers, returns the combined answer once every session replied.
+    fn collect_broadcast(
+        &self,
+        index: usize,
+        extra: &str,
+        msg: String,
+        is_error: bool,
+    ) -> Option<Option<String>> {
+        let mut broadcasts = self.broadcasts.lock().unwrap();
+        let broadcast = broadcasts.get_mut(extra)?;
+        if !broadcast.pending.remove(&index) {
+            warn!("pool session {index} answered a broadcast it wasn't sent");
+            return Some(None);
+        }
+        if broadcast.init {
+            // a session that can't init is useless, a later init retry may revive it
+            let session = &self.sessions[index];
+            session.healthy.store(!is_error, Ordering::SeqCst);
+            session.initialized.store(!is_error, Ordering::SeqCst);
+            session.failures.store(0, Ordering::SeqCst);
+            *session.checked_at.lock().unwrap() = Instant::now();
+        }
+        if is_error {
+            warn!("pool session {index} {msg}");
+            broadcast.error = Some(msg);
+        } else {
+            broadcast.success = Some(msg);
+        }
+
+        if !broadcast.pending.is_empty() {
+            return Some(None);
+        }
+        let broadcast = broadcasts.remove(extra).unwrap();
+        return Some(broadcast.success.or(broadcast.error));
+    }
+}
+
+impl<T: Transport> Transport for PoolTransport<T> {
+    fn send(&self, request: &str) -> Result<()> {
+        let mut value: serde_json::Value = serde_json::from_str(request)?;
+        if BROADCAST_TYPES.contains(&value["@type"].as_str().unwrap_or_default()) {
+            return self.broadcast(&value);
+        }
+
+        // session objects and keys are reachable even when their session is out of rotation
+        let index = match self.handle_session(&mut value)? {
+            Some(index) => index,
+            None if KEYSTORE_TYPES.contains(&value["@type"].as_str().unwrap_or_default()) => 0,
+            None => self.pick()?,
+        };
+        let session = &self.sessions[index];
+        if let Some(extra) = value["@extra"].as_str() {
+            self.routes
+                .lock()
+                .unwrap()
+                .insert(extra.to_string(), Route { session: index });
+            session.in_flight.fetch_add(1, Ordering::SeqCst);
+        }
+        debug!("pool session {index} send request");
+        session.transport.send(&value.to_string())
+    }
+
+    fn receive(&self) -> Option<String> {
+        self.recheck_sessions();
+
+        let (index, msg) = self
+            .messages
+            .recv_timeout(Duration::from_millis(100))
+            .ok()?;
+        let mut value: serde_json::Value = match serde_json::from_str(&msg) {
+            Ok(value) => value,
+            Err(err) => {
+                error!("pool session {index} sent invalid json: {err}");
+                return None;
+            }
+        };
+        let extra = match value["@extra"].as_str() {
+            Some(extra) => extra.to_string(),
+            None => return Some(msg),
+        };
+        let is_error = value["@type"] == "error";
+
+        if let Some(index) = extra.strip_prefix(PROBE_EXTRA) {
+            if let Ok(index) = index.parse() {
+                self.probed(index, &msg, is_error);
+            }
+            return None;
+        }
+        if let Some(answer) = self.collect_broadcast(index, &extra, msg.clone(), is_error) {
+            return answer;
+        }
+
+        if let Some(route) = self.routes.lock().unwrap().remove(&extra) {
+            self.sessions[route.session]
+                .in_flight
+                .fetch_sub(1, Ordering::SeqCst);
+            match is_error && Error::from_tonlib(&value).is_connection_error() {
+                true => self.record_failure(route.session, &msg),
+                false => self.record_success(route.session),
+            }
+            if self.register_handle(index, &mut value) {
+                return Some(value.to_string());
+            }
+        }
+        return Some(msg);
+    }
+
+    fn execute(&self, request: &str) -> Result<Option<String>> {
+        let index = self.pick()?;
+        self.sessions[index].transport.execute(request)
+    }
+
+    fn timed_out(&self, extra: &str) {
+        if let Some(route) = self.routes.lock().unwrap().remove(extra) {
+            self.sessions[route.session]
+                .in_flight
+                .fetch_sub(1, Ordering::SeqCst);
+            self.record_failure(route.session, "timeout");
+        }
+        if let Some(broadcast) = self.broadcasts.lock().unwrap().remove(extra) {
+            for index in broadcast.pending {
+                warn!("pool session {index} didn't answer a broadcast in time");
+                let session = &self.sessions[index];
+                if broadcast.init {
+                    session.initialized.store(false, Ordering::SeqCst);
+                    session.healthy.store(false, Ordering::SeqCst);
+                    *session.checked_at.lock().unwrap() = Instant::now();
+                }
+            }
+        }
+    }
+}
+
+impl<T: Transport> Drop for PoolTransport<T> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        for pump in self.pumps.drain(..) {
+            if pump.join().is_err() {
+                error!("pool session receive looper panicked");
+            }
+        }
+    }
+}
+
+/// Splits `config` into `size` configs with one lite server each.
+fn pinned_configs(config: &str, size: usize) -> Result<Vec<Option<String>>> {
+    let (config, liteservers) = parse_lite_servers(config)?;
+
+    let configs = (0..size)
+        .map(|index| {
+            let mut pinned = config.clone();
+            pinned["liteservers"] = serde_json::json!([liteservers[index % liteservers.len()]]);
+            Some(pinned.to_string())
+        })
+        .collect();
+    return Ok(configs);
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        client::{ClientBuilder, RetryPolicy, ScriptedTransport, TonLibLog},
+        request, types,
+    };
+
+    const CONFIG: &str = r#"{"liteservers": [{"ip": 1, "port": 1}, {"ip": 2, "port": 2}]}"#;
+
+    fn config() -> Config {
+        return ClientBuilder::new()
+            .lite_server_config(CONFIG)
+            .request_timeout(Duration::from_secs(2))
+            .tonlib_log(TonLibLog::Discard)
+            .init_retry(RetryPolicy::no_retry())
+            .build_config()
+            .unwrap();
+    }
+
+    fn session() -> ScriptedTransport {
+        let transport = ScriptedTransport::new();
+        transport.reply(
+            "blocks.getMasterchainInfo",
+            json!({
+              "@type": "blocks.masterchainInfo",
+              "init": block(),
+              "last": block(),
+              "state_root_hash": ""
+            }),
+        );
+        return transport;
+    }
+
+    fn block() -> serde_json::Value {
+        return json!({
+          "@type": "ton.blockIdExt",
+          "workchain": -1,
+          "shard": "-9223372036854775808",
+          "seqno": 1,
+          "root_hash": "",
+          "file_hash": ""
+        });
+    }
+
+    fn pool(
+        sessions: Vec<ScriptedTransport>,
+        options: PoolOptions,
+    ) -> ClientPool<ScriptedTransport> {
+        let config = config();
+        let transport = PoolTransport::with_sessions(&config, options, sessions).unwrap();
+        return Client::with_transport(&config, transport).unwrap();
+    }
+
+    fn options(balancing: Balancing) -> PoolOptions {
+        return PoolOptions {
+            balancing,
+            ..PoolOptions::default()
+        };
+    }
+
+    fn session_transport(pool: &ClientPool<ScriptedTransport>, index: usize) -> &ScriptedTransport {
+        return &pool.transport().sessions[index].transport;
+    }
+
+    fn sent_to(
+        pool: &ClientPool<ScriptedTransport>,
+        request_type: &str,
+    ) -> Vec<Vec<serde_json::Value>> {
+        return (0..pool.transport().sessions.len())
+            .map(|index| {
+                session_transport(pool, index)
+                    .sent()
+                    .into_iter()
+                    .filter(|request| request["@type"] == request_type)
+                    .collect()
+            })
+            .collect();
+    }
+
+    fn counts(pool: &ClientPool<ScriptedTransport>, request_type: &str) -> Vec<usize> {
+        return sent_to(pool, request_type)
+            .iter()
+            .map(|requests| requests.len())
+            .collect();
+    }
+
+    fn connection_error() -> serde_json::Value {
+        return json!({ "@type": "error", "code": 652, "message": "LITE_SERVER_NETWORK" });
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) {
+        let started = Instant::now();
+        while !condition() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "condition not met"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn round_robin_spreads_requests() {
+        let pool = pool(
+            vec![session(), session(), session()],
+            options(Balancing::RoundRobin),
+        );
+        for _ in 0..6 {
+            block_on(pool.get_master_chain_info()).unwrap();
+        }
+        assert_eq!(counts(&pool, "blocks.getMasterchainInfo"), [2, 2, 2]);
+    }
+
+    #[test]
+    fn least_in_flight_prefers_idle_sessions() {
+        let sessions = vec![session(), session(), session()];
+        for session in &sessions {
+            session.on("blocks.getMasterchainInfo", |_| serde_json::Value::Null);
+        }
+        let pool = pool(sessions, options(Balancing::LeastInFlight));
+
+        let pending: Vec<_> = (0..4)
+            .map(|_| pool.send(request::MasterChainInfo {}).unwrap())
+            .collect();
+        assert_eq!(counts(&pool, "blocks.getMasterchainInfo"), [2, 1, 1]);
+        drop(pending);
+    }
+
+    #[test]
+    fn evicts_session_after_connection_errors() {
+        let sessions = vec![session(), session()];
+        sessions[0].reply("blocks.getMasterchainInfo", connection_error());
+        sessions[0].reply(
+            "sync",
+            json!({ "@type": "error", "code": 400, "message": "bad request" }),
+        );
+        sessions[1].reply("sync", block());
+        let pool = pool(
+            sessions,
+            PoolOptions {
+                max_failures: 2,
+                recheck_interval: Duration::from_secs(60),
+                ..options(Balancing::RoundRobin)
+            },
+        );
+
+        // request errors don't count as session failures
+        for _ in 0..4 {
+            _ = block_on(pool.sync());
+        }
+        assert_eq!(pool.healthy_sessions(), 2);
+
+        for _ in 0..4 {
+            _ = block_on(pool.get_master_chain_info());
+        }
+        assert_eq!(pool.healthy_sessions(), 1);
+        assert_eq!(counts(&pool, "blocks.getMasterchainInfo"), [2, 2]);
+        for _ in 0..3 {
+            block_on(pool.get_master_chain_info()).unwrap();
+        }
+        assert_eq!(counts(&pool, "blocks.getMasterchainInfo"), [2, 5]);
+    }
+
+    #[test]
+    fn probe_readmits_recovered_session() {
+        let sessions = vec![session(), session()];
+        sessions[0].reply("blocks.getMasterchainInfo", connection_error());
+        let pool = pool(
+            sessions,
+            PoolOptions {
+                max_failures: 1,
+                recheck_interval: Duration::from_millis(50),
+                ..options(Balancing::RoundRobin)
+            },
+        );
+
+        _ = block_on(pool.get_master_chain_info());
+        assert_eq!(pool.healthy_sessions(), 1);
+
+        // still failing probes keep the session out
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(pool.healthy_sessions(), 1);
+        let probes = sent_to(&pool, "blocks.getMasterchainInfo")[0]
+            .iter()
+            .filter(|request| request["@extra"] == "pool-probe-0")
+            .count();
+        assert!(probes > 0);
+
+        session_transport(&pool, 0).reply(
+            "blocks.getMasterchainInfo",
+            json!({
+              "@type": "blocks.masterchainInfo",
+              "init": block(),
+              "last": block(),
+              "state_root_hash": ""
+            }),
+        );
+        wait_for(|| pool.healthy_sessions() == 2);
+    }
+
+    #[test]
+    fn probe_reinits_session_that_failed_init() {
+        let sessions = vec![session(), session()];
+        sessions[1].reply(
+            "init",
+            json!({ "@type": "error", "code": 500, "message": "no lite server" }),
+        );
+        let pool = pool(
+            sessions,
+            PoolOptions {
+                recheck_interval: Duration::from_millis(50),
+                ..options(Balancing::RoundRobin)
+            },
+        );
+        assert_eq!(pool.healthy_sessions(), 1);
+
+        session_transport(&pool, 1).reply(
+            "init",
+            json!({ "@type": "options.info", "config_info": {} }),
+        );
+        wait_for(|| pool.healthy_sessions() == 2);
+
+        // each init carries the session's pinned lite server
+        let inits = sent_to(&pool, "init");
+        let pinned: serde_json::Value =
+            serde_json::from_str(inits[1][0]["options"]["config"]["config"].as_str().unwrap())
+                .unwrap();
+        assert_eq!(pinned["liteservers"], json!([{ "ip": 2, "port": 2 }]));
+        assert_eq!(inits[1].last().unwrap()["@extra"], "pool-probe-1");
+    }
+
+    #[test]
+    fn remaps_smc_ids_to_loading_session() {
+        let sessions = vec![session(), session()];
+        for session in &sessions {
+            session.reply("smc.load", json!({ "@type": "smc.info", "id": 1 }));
+            session.reply("smc.forget", json!({ "@type": "ok" }));
+        }
+        let pool = pool(sessions, options(Balancing::RoundRobin));
+
+        let first = block_on(pool.smc_load("EQfirst")).unwrap();
+        let second = block_on(pool.smc_load("EQsecond")).unwrap();
+        assert_ne!(first, second);
+
+        // round robin would pick session 0 next, the contract lives in session 1
+        block_on(pool.smc_forget(second)).unwrap();
+        let forgets = sent_to(&pool, "smc.forget");
+        assert!(forgets[0].is_empty());
+        assert_eq!(forgets[1][0]["id"], 1);
+
+        // forgotten ids are unknown
+        assert!(matches!(
+            block_on(pool.smc_forget(second)),
+            Err(Error::InvalidInput(_))
+        ));
+        block_on(pool.smc_forget(first)).unwrap();
+        assert_eq!(counts(&pool, "smc.forget"), [1, 1]);
+    }
+
+    #[test]
+    fn remaps_query_ids() {
+        let sessions = vec![session(), session()];
+        for session in &sessions {
+            session.reply(
+                "createQuery",
+                json!({
+                  "@type": "query.info",
+                  "id": 7,
+                  "valid_until": 0,
+                  "body_hash": "",
+                  "body": "",
+                  "init_state": ""
+                }),
+            );
+            session.reply("query.send", json!({ "@type": "ok" }));
+        }
+        let pool = pool(sessions, options(Balancing::RoundRobin));
+
+        let query = block_on(pool.create_query(
+            &types::InputKey::Fake,
+            "EQwallet",
+            vec![types::Message::new("EQdestination", 1)],
+            false,
+            None,
+        ))
+        .unwrap();
+        for _ in 0..2 {
+            block_on(pool.query_send(query.id)).unwrap();
+        }
+        let sends = sent_to(&pool, "query.send");
+        assert_eq!(sends[0].len(), 2);
+        assert!(sends[0].iter().all(|request| request["id"] == 7));
+        assert!(sends[1].is_empty());
+    }
+
+    #[test]
+    fn keystore_requests_go_to_first_session() {
+        let sessions = vec![session(), session(), session()];
+        for session in &sessions {
+            session.reply(
+                "createNewKey",
+                json!({ "@type": "key", "public_key": "", "secret": "" }),
+            );
+        }
+        let pool = pool(sessions, options(Balancing::RoundRobin));
+
+        for _ in 0..3 {
+            block_on(pool.create_new_key(
+                &types::SecureBytes::default(),
+                &types::SecureBytes::default(),
+            ))
+            .unwrap();
+        }
+        assert_eq!(counts(&pool, "createNewKey"), [3, 0, 0]);
+    }
+
+    #[test]
+    fn broadcasts_init_and_close() {
+        let sessions = vec![session(), session(), session()];
+        sessions[2].reply(
+            "init",
+            json!({ "@type": "error", "code": 500, "message": "no lite server" }),
+        );
+        let pool = pool(sessions, options(Balancing::RoundRobin));
+
+        // one session answering init is enough
+        assert_eq!(counts(&pool, "init"), [1, 1, 1]);
+        assert_eq!(pool.healthy_sessions(), 2);
+
+        pool.close().unwrap();
+        assert_eq!(counts(&pool, "close"), [1, 1, 0]);
+        assert!(pool.transport().broadcasts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fails_when_every_session_fails_init() {
+        let sessions = vec![session(), session()];
+        for session in &sessions {
+            session.reply(
+                "init",
+                json!({ "@type": "error", "code": 400, "message": "invalid config" }),
+            );
+        }
+        let config = config();
+        let transport = PoolTransport::with_sessions(&config, PoolOptions::default(), sessions);
+        let result = Client::with_transport(&config, transport.unwrap());
+        assert!(matches!(result, Err(Error::Init { .. })));
+    }
+
+    #[test]
+    fn unanswered_broadcast_is_dropped_on_timeout() {
+        let sessions = vec![session(), session()];
+        sessions[1].on("init", |_| serde_json::Value::Null);
+        let config = Config {
+            request_timeout: Duration::from_millis(100),
+            ..config()
+        };
+        let transport = Arc::new(
+            PoolTransport::with_sessions(&config, PoolOptions::default(), sessions).unwrap(),
+        );
+
+        let result = Client::with_transport(&config, transport.clone());
+        assert!(matches!(result, Err(Error::Init { .. })));
+        assert!(transport.broadcasts.lock().unwrap().is_empty());
+        // the silent session waits for its init probe
+        assert_eq!(transport.healthy_sessions(), 1);
+        assert!(!transport.sessions[1].initialized.load(Ordering::SeqCst));
+    }
+}
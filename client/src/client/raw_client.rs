@@ -1,75 +1,121 @@
-use std::cell::Cell;
+use std::sync::Arc;
 
-use super::interop;
+use zeroize::Zeroize;
 
+use crate::{Error, Result};
+
+use super::{interop, Transport};
+
+/// Thin wrapper over a tonlib json client.
+///
+/// tonlib allows `send` and `execute` to be called from any thread, while `receive`
+/// must only be called from one thread at a time (the receive looper).
 pub struct RawClient {
+    tonlib: Arc<interop::TonLib>,
     timeout: std::time::Duration,
-    client: Cell<*mut std::ffi::c_void>,
+    client: interop::Handle,
 }
 
 impl RawClient {
-    pub fn new(log_level: i8) -> Self {
+    /// Creates a client on the library found with the default `LoadOptions`.
+    pub fn new(log_level: i8) -> Result<Self> {
+        Ok(Self::with_library(interop::TonLib::global()?, log_level))
+    }
+
+    pub fn with_library(tonlib: Arc<interop::TonLib>, log_level: i8) -> Self {
+        let client = unsafe {
+            (tonlib.set_verbosity_level)(log_level as i32);
+            (tonlib.json_create)()
+        };
         Self {
-            timeout: std::time::Duration::from_millis(1),
-            client: Cell::new(unsafe {
-                interop::TONLIB_CLIENT_SET_VERBOSITY_LEVEL(log_level as i32);
-                interop::TONLIB_CLIENT_JSON_CREATE()
-            }),
+            tonlib,
+            timeout: std::time::Duration::from_millis(100),
+            client,
         }
     }
 
-    pub fn send(&mut self, req: &str) {
-        self._send(req);
+    fn _send(&self, req: &str) -> Result<()> {
+        let mut item = c_string(req)?;
+        unsafe { (self.tonlib.json_send)(self.client, item.as_ptr()) };
+        item.zeroize();
+        Ok(())
     }
 
-    pub fn receive(&mut self) -> Option<String> {
-        self._receive()
+    /// Runs a static tonlib method, one that doesn't need a client instance.
+    pub fn execute_static(req: &str) -> Result<Option<String>> {
+        let tonlib = interop::TonLib::global()?;
+        Self::_execute_with(&tonlib, std::ptr::null_mut(), req)
     }
 
-    fn _send(&mut self, req: &str) {
-        unsafe {
-            let item = std::ffi::CString::new(req).unwrap();
-            interop::TONLIB_CLIENT_JSON_SEND(self.client.get(), item.as_ptr())
-        };
+    fn _execute(&self, req: &str) -> Result<Option<String>> {
+        Self::_execute_with(&self.tonlib, self.client, req)
     }
 
-    fn _execute(&mut self, req: &str) -> Option<String> {
+    fn _execute_with(
+        tonlib: &interop::TonLib,
+        client: interop::Handle,
+        req: &str,
+    ) -> Result<Option<String>> {
+        let mut item = c_string(req)?;
         unsafe {
-            let item = std::ffi::CString::new(req).unwrap();
             let response_buf: *const std::ffi::c_char =
-                interop::TONLIB_CLIENT_JSON_EXECUTE(self.client.get(), item.as_ptr());
+                (tonlib.json_execute)(client, item.as_ptr());
+            item.zeroize();
 
             if response_buf.is_null() {
-                return None;
+                return Ok(None);
             }
 
             let response_str = std::ffi::CStr::from_ptr(response_buf);
-            return Some(response_str.to_str().unwrap().to_owned());
+            return Ok(Some(response_str.to_string_lossy().into_owned()));
         }
     }
 
-    fn _receive(&mut self) -> Option<String> {
+    fn _receive(&self) -> Option<String> {
         unsafe {
             let response_buf: *const std::ffi::c_char =
-                interop::TONLIB_CLIENT_JSON_RECEIVE(self.client.get(), self.timeout.as_secs_f64());
+                (self.tonlib.json_receive)(self.client, self.timeout.as_secs_f64());
             if response_buf.is_null() {
                 return None;
             }
 
             let response_str = std::ffi::CStr::from_ptr(response_buf);
-            return Some(response_str.to_str().unwrap().to_owned());
+            return Some(response_str.to_string_lossy().into_owned());
         }
     }
 }
 
+impl Transport for RawClient {
+    fn send(&self, request: &str) -> Result<()> {
+        self._send(request)
+    }
+
+    fn receive(&self) -> Option<String> {
+        self._receive()
+    }
+
+    fn execute(&self, request: &str) -> Result<Option<String>> {
+        self._execute(request)
+    }
+}
+
 impl Drop for RawClient {
     fn drop(&mut self) {
-        drop(self.timeout);
         unsafe {
-            interop::TONLIB_CLIENT_JSON_DESTROY(self.client.get());
+            (self.tonlib.json_destroy)(self.client);
         }
-        drop(self.client.get_mut());
     }
 }
 
+/// Copies `req` for tonlib, the copy is zeroized by the caller once tonlib is done with it.
+fn c_string(req: &str) -> Result<std::ffi::CString> {
+    return std::ffi::CString::new(req).map_err(|err| {
+        let message = err.to_string();
+        // the error owns a copy of the request too
+        err.into_vec().zeroize();
+        Error::InvalidInput(message)
+    });
+}
+
 unsafe impl Send for RawClient {}
+unsafe impl Sync for RawClient {}
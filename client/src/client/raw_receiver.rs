@@ -1,98 +1,326 @@
 use std::{
     collections::BTreeMap,
-    sync::{Arc, Mutex},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
-use log::trace;
+use futures::channel::{mpsc, oneshot};
+use log::{error, trace};
+use zeroize::Zeroizing;
 
-use super::RawClient;
+use crate::{types, Error, Result};
 
-type ReceiverDataResult = Option<Result<serde_json::Value, String>>;
-type ReceiveResult = Result<serde_json::Value, String>;
+use super::Transport;
 
-pub struct RawReceiver {
-    raw_client: Arc<Mutex<RawClient>>,
-    data: Arc<Mutex<BTreeMap<String, ReceiverDataResult>>>,
-    timeout: std::time::Duration,
+type ReceiveResult = Result<serde_json::Value>;
+
+/// How often the receive thread looks for tasks whose deadline has passed.
+const EXPIRE_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+struct Task {
+    sender: oneshot::Sender<ReceiveResult>,
+    deadline: Instant,
+}
+
+type Tasks = Arc<Mutex<BTreeMap<String, Task>>>;
+type Subscribers = Arc<Mutex<Vec<mpsc::UnboundedSender<types::Update>>>>;
+
+/// Stream of tonlib updates returned by `Client::subscribe_updates`.
+pub type Updates = mpsc::UnboundedReceiver<types::Update>;
+
+pub struct RawReceiver<T: Transport> {
+    transport: Arc<T>,
+    tasks: Tasks,
+    subscribers: Subscribers,
+    timeout: Duration,
+    running: Arc<AtomicBool>,
+    looper: Mutex<Option<std::thread::JoinHandle<()>>>,
+}
+
+/// Resolves with the tonlib response for a single `@extra`.
+///
+/// The future is completed directly by the receive thread, so polling it never blocks.
+/// Dropping it before completion forgets the request, a late answer is discarded.
+pub struct ResponseFuture {
+    receiver: oneshot::Receiver<ReceiveResult>,
+    cancel_handle: CancelHandle,
+}
+
+impl ResponseFuture {
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+}
+
+impl Drop for ResponseFuture {
+    fn drop(&mut self) {
+        self.cancel_handle.forget();
+    }
 }
 
-impl RawReceiver {
-    pub fn new(raw_client: Arc<Mutex<RawClient>>, timeout: std::time::Duration) -> Self {
+/// Cancels a pending request, its future resolves with `Error::Cancelled`.
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    extra: String,
+    tasks: Weak<Mutex<BTreeMap<String, Task>>>,
+}
+
+impl CancelHandle {
+    /// A handle for a request that never became pending.
+    pub(crate) fn completed() -> Self {
         Self {
-            raw_client,
-            data: Arc::new(Mutex::new(BTreeMap::new())),
-            timeout,
+            extra: String::new(),
+            tasks: Weak::new(),
+        }
+    }
+
+    /// Returns `false` if the request already completed.
+    pub fn cancel(&self) -> bool {
+        match self.take() {
+            Some(task) => {
+                _ = task.sender.send(Err(Error::Cancelled));
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn add_task(&mut self, extra: &str) {
-        self.data.lock().unwrap().insert(extra.to_string(), None);
+    fn forget(&self) {
+        _ = self.take();
     }
 
-    pub fn receive(&mut self, extra: &str) -> impl futures::Future<Output = ReceiveResult> {
-        let data = Arc::clone(&self.data);
-        let timeout = self.timeout;
-        let extra_str = extra.to_string();
+    fn take(&self) -> Option<Task> {
+        let tasks = self.tasks.upgrade()?;
+        let task = tasks.lock().unwrap().remove(&self.extra);
+        task
+    }
+}
 
-        async move {
-            let stop_time = std::time::Instant::now() + timeout;
-            while std::time::Instant::now() < stop_time {
-                match data.try_lock() {
-                    Ok(mut data_unwrapped) => {
-                        match data_unwrapped.get(&extra_str) {
-                            Some(Some(_)) => {
-                                return data_unwrapped.remove(&extra_str).unwrap().unwrap();
-                            }
-                            _ => {}
-                        };
-                    }
-                    _ => {}
-                };
-                std::thread::sleep(std::time::Duration::from_millis(2));
-            }
-            _ = data.lock().unwrap().remove(&extra_str).unwrap();
-            return Err("timeout".to_string());
+impl futures::Future for ResponseFuture {
+    type Output = ReceiveResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match Pin::new(&mut self.receiver).poll(cx) {
+            Poll::Ready(Ok(result)) => Poll::Ready(result),
+            // the receiver was stopped before an answer arrived
+            Poll::Ready(Err(oneshot::Canceled)) => Poll::Ready(Err(Error::Closed)),
+            Poll::Pending => Poll::Pending,
         }
     }
+}
 
-    pub fn start(&mut self) {
-        let raw_client = self.raw_client.clone();
-        let data = self.data.clone();
-        std::thread::Builder::new()
-            .name("raw_client_receive_looper".to_owned())
-            .spawn(move || loop {
-                std::thread::sleep(std::time::Duration::from_millis(2));
+impl<T: Transport> RawReceiver<T> {
+    pub fn new(transport: Arc<T>, timeout: Duration) -> Self {
+        Self {
+            transport,
+            tasks: Arc::new(Mutex::new(BTreeMap::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            timeout,
+            running: Arc::new(AtomicBool::new(false)),
+            looper: Mutex::new(None),
+        }
+    }
 
-                let msg = match raw_client.try_lock() {
-                    Ok(mut cl) => cl.receive(),
-                    _ => continue,
-                };
+    /// Registers a pending request, `timeout` overrides the receiver default.
+    pub fn add_task(&self, extra: &str, timeout: Option<Duration>) -> ResponseFuture {
+        let (sender, receiver) = oneshot::channel();
+        let task = Task {
+            sender,
+            deadline: Instant::now() + timeout.unwrap_or(self.timeout),
+        };
+        self.tasks.lock().unwrap().insert(extra.to_string(), task);
+        ResponseFuture {
+            receiver,
+            cancel_handle: CancelHandle {
+                extra: extra.to_string(),
+                tasks: Arc::downgrade(&self.tasks),
+            },
+        }
+    }
 
-                if msg.is_none() {
-                    continue;
-                }
+    pub fn subscribe(&self) -> Updates {
+        let (sender, receiver) = mpsc::unbounded();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
 
-                trace!("reiceived msg: {:?}", msg);
-                let json_msg: serde_json::Value = serde_json::from_str(&msg.unwrap()).unwrap();
-                let extra_data = json_msg["@extra"].clone();
-                let is_error = match json_msg["@type"].as_str() {
-                    Some("error") => true,
-                    _ => false,
-                };
-                if extra_data.is_string() {
-                    let extra = extra_data.as_str().unwrap();
-                    let mut d = data.lock().unwrap();
-                    if !d.contains_key(extra) {
-                        continue;
+    pub fn start(&self) {
+        let transport = self.transport.clone();
+        let tasks = self.tasks.clone();
+        let subscribers = self.subscribers.clone();
+        let running = self.running.clone();
+        running.store(true, Ordering::SeqCst);
+        let looper = std::thread::Builder::new()
+            .name("raw_client_receive_looper".to_owned())
+            .spawn(move || {
+                let mut next_expire_check = Instant::now() + EXPIRE_CHECK_INTERVAL;
+                while running.load(Ordering::SeqCst) {
+                    if let Some(msg) = transport.receive() {
+                        // keystore answers carry secrets, wiped once decoded
+                        Self::dispatch(&tasks, &subscribers, &Zeroizing::new(msg));
                     }
 
-                    let res = match !is_error {
-                        true => Ok(json_msg),
-                        false => Err(json_msg["message"].as_str().unwrap_or_default().to_string()),
-                    };
-                    d.insert(extra.to_string(), Some(res));
+                    let now = Instant::now();
+                    if now >= next_expire_check {
+                        Self::expire(&transport, &tasks, now);
+                        next_expire_check = now + EXPIRE_CHECK_INTERVAL;
+                    }
                 }
             })
             .unwrap();
+        *self.looper.lock().unwrap() = Some(looper);
+    }
+
+    /// Stops the receive thread, waits for it to exit and resolves every pending
+    /// task with `Error::Closed`.
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(looper) = self.looper.lock().unwrap().take() {
+            if looper.join().is_err() {
+                error!("receive looper panicked");
+            }
+        }
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for (_, task) in tasks {
+            _ = task.sender.send(Err(Error::Closed));
+        }
+        self.subscribers.lock().unwrap().clear();
+    }
+
+    fn dispatch(tasks: &Tasks, subscribers: &Subscribers, msg: &str) {
+        let json_msg: serde_json::Value = match serde_json::from_str(msg) {
+            Ok(json_msg) => json_msg,
+            Err(err) => {
+                error!("can't parse tonlib message: {err}");
+                return;
+            }
+        };
+        let sensitive = types::is_sensitive(&json_msg);
+        match sensitive {
+            true => trace!("reiceived msg: {}: <redacted>", json_msg["@extra"]),
+            false => trace!("reiceived msg: {:?}", msg),
+        }
+        let task = match json_msg["@extra"].as_str() {
+            Some(extra) => tasks.lock().unwrap().remove(extra),
+            None => None,
+        };
+        let task = match task {
+            Some(task) => task,
+            None => {
+                if types::Update::is_update(&json_msg) {
+                    Self::publish(subscribers, json_msg);
+                } else if sensitive {
+                    trace!("drop unexpected msg: {}: <redacted>", json_msg["@extra"]);
+                } else {
+                    trace!("drop unexpected msg: {msg}");
+                }
+                return;
+            }
+        };
+
+        let res = match json_msg["@type"].as_str() {
+            Some("error") => Err(Error::from_tonlib(&json_msg)),
+            _ => Ok(json_msg),
+        };
+        // the caller may have stopped waiting, nobody to notify then
+        _ = task.sender.send(res);
+    }
+
+    fn publish(subscribers: &Subscribers, update: serde_json::Value) {
+        let update = types::Update::from_value(update);
+        subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.unbounded_send(update.clone()).is_ok());
+    }
+
+    fn expire(transport: &T, tasks: &Tasks, now: Instant) {
+        let expired: Vec<(String, Task)> = {
+            let mut tasks = tasks.lock().unwrap();
+            let extras: Vec<String> = tasks
+                .iter()
+                .filter(|(_, task)| task.deadline <= now)
+                .map(|(extra, _)| extra.clone())
+                .collect();
+            extras
+                .into_iter()
+                .filter_map(|extra| tasks.remove(&extra).map(|task| (extra, task)))
+                .collect()
+        };
+        for (extra, task) in expired {
+            trace!("task timeout: {extra}");
+            _ = task.sender.send(Err(Error::Timeout));
+            transport.timed_out(&extra);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::client::ScriptedTransport;
+
+    fn receiver() -> RawReceiver<ScriptedTransport> {
+        let transport = ScriptedTransport::new();
+        transport.on("blocks.getMasterchainInfo", |_| serde_json::Value::Null);
+        transport.reply("ping", serde_json::json!({ "@type": "ok" }));
+        let receiver = RawReceiver::new(Arc::new(transport), Duration::from_secs(5));
+        receiver.start();
+        return receiver;
+    }
+
+    fn send(receiver: &RawReceiver<ScriptedTransport>, request_type: &str, extra: &str) {
+        let request = serde_json::json!({ "@type": request_type, "@extra": extra });
+        receiver.transport.send(&request.to_string()).unwrap();
+    }
+
+    #[test]
+    fn resolves_answered_task() {
+        let receiver = receiver();
+        let response = receiver.add_task("1", None);
+        send(&receiver, "ping", "1");
+        assert_eq!(block_on(response).unwrap()["@type"], "ok");
+        assert!(receiver.tasks.lock().unwrap().is_empty());
+        receiver.stop();
+    }
+
+    #[test]
+    fn expires_task_after_its_timeout() {
+        let receiver = receiver();
+        let response = receiver.add_task("1", Some(Duration::from_millis(100)));
+        send(&receiver, "blocks.getMasterchainInfo", "1");
+        assert!(matches!(block_on(response), Err(Error::Timeout)));
+        assert!(receiver.tasks.lock().unwrap().is_empty());
+        receiver.stop();
+    }
+
+    #[test]
+    fn dropped_future_forgets_task() {
+        let receiver = receiver();
+        let response = receiver.add_task("1", None);
+        let cancel_handle = response.cancel_handle();
+        drop(response);
+        assert!(receiver.tasks.lock().unwrap().is_empty());
+        assert!(!cancel_handle.cancel());
+
+        // a late answer is dropped
+        send(&receiver, "ping", "1");
+        receiver.stop();
+    }
+
+    #[test]
+    fn stop_closes_pending_tasks() {
+        let receiver = receiver();
+        let response = receiver.add_task("1", None);
+        receiver.stop();
+        assert!(matches!(block_on(response), Err(Error::Closed)));
     }
 }
@@ -0,0 +1,235 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use serde_json::json;
+
+use crate::{Error, Result};
+
+use super::Transport;
+
+type Handler = Box<dyn FnMut(&serde_json::Value) -> serde_json::Value + Send>;
+
+/// In-process `Transport` answering requests from handlers registered per `@type`.
+///
+/// Lets code built on `Client` be tested without a native tonlib build or network:
+///
+/// ```ignore
+/// let transport = ScriptedTransport::new();
+/// transport.reply("blocks.getMasterchainInfo", json!({ "@type": "blocks.masterchainInfo", ... }));
+/// let client = Client::with_transport(&config, transport)?;
+/// ```
+///
+/// `init`, `close` and the log settings are answered with successful responses unless
+/// scripted otherwise.
+/// Requests without a handler are answered with a tonlib `error`.
+pub struct ScriptedTransport {
+    handlers: Mutex<BTreeMap<String, Handler>>,
+    sent: Mutex<Vec<serde_json::Value>>,
+    queue: Mutex<VecDeque<String>>,
+    queue_changed: Condvar,
+    receive_timeout: Duration,
+}
+
+impl ScriptedTransport {
+    pub fn new() -> Self {
+        let transport = Self {
+            handlers: Mutex::new(BTreeMap::new()),
+            sent: Mutex::new(Vec::new()),
+            queue: Mutex::new(VecDeque::new()),
+            queue_changed: Condvar::new(),
+            receive_timeout: Duration::from_millis(10),
+        };
+        transport.reply(
+            "init",
+            json!({
+              "@type": "options.info",
+              "config_info": {
+                "@type": "options.configInfo",
+                "default_wallet_id": 0,
+                "default_rwallet_init_public_key": ""
+              }
+            }),
+        );
+        transport.reply("close", json!({ "@type": "ok" }));
+        for request_type in [
+            "setLogStream",
+            "setLogVerbosityLevel",
+            "setLogTagVerbosityLevel",
+        ] {
+            transport.reply(request_type, json!({ "@type": "ok" }));
+        }
+        return transport;
+    }
+
+    /// Answers every request of `request_type` with the result of `handler`.
+    ///
+    /// A `null` result leaves the request unanswered, useful to exercise timeouts.
+    pub fn on(
+        &self,
+        request_type: &str,
+        handler: impl FnMut(&serde_json::Value) -> serde_json::Value + Send + 'static,
+    ) {
+        self.handlers
+            .lock()
+            .unwrap()
+            .insert(request_type.to_string(), Box::new(handler));
+    }
+
+    /// Answers every request of `request_type` with `response`.
+    pub fn reply(&self, request_type: &str, response: serde_json::Value) {
+        self.on(request_type, move |_| response.clone());
+    }
+
+    /// Delivers a message that isn't an answer to any request, like tonlib `update*` objects.
+    pub fn push(&self, message: serde_json::Value) {
+        self.enqueue(message.to_string());
+    }
+
+    /// Returns every request sent or executed so far.
+    pub fn sent(&self) -> Vec<serde_json::Value> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    fn enqueue(&self, message: String) {
+        self.queue.lock().unwrap().push_back(message);
+        self.queue_changed.notify_one();
+    }
+
+    fn answer(&self, request: &str) -> Result<serde_json::Value> {
+        let request: serde_json::Value = serde_json::from_str(request)?;
+        self.sent.lock().unwrap().push(request.clone());
+
+        let request_type = request["@type"].as_str().unwrap_or_default();
+        let mut response = match self.handlers.lock().unwrap().get_mut(request_type) {
+            Some(handler) => handler(&request),
+            None => json!({
+              "@type": "error",
+              "code": 400,
+              "message": format!("no scripted response for {request_type}")
+            }),
+        };
+        if !response.is_null() && !request["@extra"].is_null() {
+            response["@extra"] = request["@extra"].clone();
+        }
+        return Ok(response);
+    }
+}
+
+impl Default for ScriptedTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for ScriptedTransport {
+    fn send(&self, request: &str) -> Result<()> {
+        let response = self
+            .answer(request)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        if !response.is_null() {
+            self.enqueue(response.to_string());
+        }
+        Ok(())
+    }
+
+    fn receive(&self) -> Option<String> {
+        let queue = self.queue.lock().unwrap();
+        let (mut queue, _) = self
+            .queue_changed
+            .wait_timeout_while(queue, self.receive_timeout, |queue| queue.is_empty())
+            .unwrap();
+        queue.pop_front()
+    }
+
+    fn execute(&self, request: &str) -> Result<Option<String>> {
+        let response = self
+            .answer(request)
+            .map_err(|e| Error::InvalidInput(e.to_string()))?;
+        if response.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(response.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receive(transport: &ScriptedTransport) -> Option<serde_json::Value> {
+        return transport
+            .receive()
+            .map(|message| serde_json::from_str(&message).unwrap());
+    }
+
+    #[test]
+    fn answers_with_extra() {
+        let transport = ScriptedTransport::new();
+        transport.reply("sync", json!({ "@type": "ton.blockIdExt" }));
+        transport
+            .send(r#"{"@type": "sync", "@extra": "1"}"#)
+            .unwrap();
+        assert_eq!(
+            receive(&transport),
+            Some(json!({ "@type": "ton.blockIdExt", "@extra": "1" }))
+        );
+        assert_eq!(receive(&transport), None);
+        assert_eq!(
+            transport.sent(),
+            [json!({ "@type": "sync", "@extra": "1" })]
+        );
+    }
+
+    #[test]
+    fn answers_unscripted_requests_with_error() {
+        let transport = ScriptedTransport::new();
+        transport
+            .send(r#"{"@type": "sync", "@extra": "1"}"#)
+            .unwrap();
+        let response = receive(&transport).unwrap();
+        assert_eq!(response["@type"], "error");
+        assert_eq!(response["code"], 400);
+        assert_eq!(response["@extra"], "1");
+    }
+
+    #[test]
+    fn null_handler_leaves_request_unanswered() {
+        let transport = ScriptedTransport::new();
+        transport.on("sync", |_| serde_json::Value::Null);
+        transport
+            .send(r#"{"@type": "sync", "@extra": "1"}"#)
+            .unwrap();
+        assert_eq!(receive(&transport), None);
+        assert_eq!(transport.execute(r#"{"@type": "sync"}"#).unwrap(), None);
+    }
+
+    #[test]
+    fn handler_sees_request() {
+        let transport = ScriptedTransport::new();
+        transport.on(
+            "getBip39Hints",
+            |request| json!({ "@type": "bip39Hints", "words": [request["prefix"]] }),
+        );
+        let response = transport
+            .execute(r#"{"@type": "getBip39Hints", "prefix": "zoo"}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&response).unwrap()["words"],
+            json!(["zoo"])
+        );
+    }
+
+    #[test]
+    fn delivers_pushed_messages() {
+        let transport = ScriptedTransport::new();
+        transport.push(json!({ "@type": "updateSyncState" }));
+        assert_eq!(
+            receive(&transport),
+            Some(json!({ "@type": "updateSyncState" }))
+        );
+    }
+}
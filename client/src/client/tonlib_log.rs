@@ -0,0 +1,323 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use log::{error, Level};
+
+use crate::Result;
+
+const TARGET: &str = "tonlib";
+const MAX_FILE_SIZE: i64 = 16 * 1024 * 1024;
+
+lazy_static! {
+    static ref FORWARDER: Mutex<Weak<Forwarder>> = Mutex::new(Weak::new());
+}
+
+/// Forwards the file tonlib logs into to the `log` crate while a client holds it.
+///
+/// tonlib's log stream is process-wide, so is the forwarder. Once the last holder
+/// releases it, the forwarding thread stops and the file is deleted.
+pub(crate) struct Forwarder {
+    path: PathBuf,
+    running: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Forwarder {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn max_file_size(&self) -> i64 {
+        MAX_FILE_SIZE
+    }
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("tonlib log forwarder panicked");
+            }
+        }
+        for path in [self.path.clone(), rotated_path(&self.path)] {
+            if let Err(err) = std::fs::remove_file(&path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    error!("can't remove tonlib log {}: {err}", path.display());
+                }
+            }
+        }
+    }
+}
+
+/// Returns the running forwarder, starting one if no client holds it.
+pub(crate) fn forwarder() -> Result<Arc<Forwarder>> {
+    let mut current = FORWARDER.lock().unwrap();
+    if let Some(forwarder) = current.upgrade() {
+        return Ok(forwarder);
+    }
+
+    let path = std::env::temp_dir().join(format!("tonlib-{}.log", std::process::id()));
+    File::create(&path)?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_path = path.clone();
+    let thread_running = running.clone();
+    let thread = std::thread::Builder::new()
+        .name("tonlib_log_forwarder".to_string())
+        .spawn(move || forward(&thread_path, &thread_running))?;
+
+    let forwarder = Arc::new(Forwarder {
+        path,
+        running,
+        thread: Some(thread),
+    });
+    *current = Arc::downgrade(&forwarder);
+    return Ok(forwarder);
+}
+
+/// Drops `forwarder`, calling `detach` first when it's the last holder so tonlib
+/// stops writing into the file before it's deleted.
+pub(crate) fn release(forwarder: Arc<Forwarder>, detach: impl FnOnce()) {
+    // no new holder while the count is checked
+    let _current = FORWARDER.lock().unwrap();
+    if Arc::strong_count(&forwarder) == 1 {
+        detach();
+    }
+    drop(forwarder);
+}
+
+/// tonlib renames a log file exceeding its max size to `<path>.old` and starts a new one.
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".old");
+    return PathBuf::from(rotated);
+}
+
+/// Tails `path` until `running` is cleared, following tonlib's rotations.
+fn forward(path: &Path, running: &AtomicBool) {
+    let mut reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(err) => {
+            error!("can't open tonlib log {}: {err}", path.display());
+            return;
+        }
+    };
+    let mut id = file_id(reader.get_ref());
+    let mut position = 0;
+    let mut level = Level::Info;
+    loop {
+        // read what was written before stopping too
+        let stopping = !running.load(Ordering::SeqCst);
+        position = match forward_lines(&mut reader, position, &mut level) {
+            Ok(position) => position,
+            Err(err) => {
+                error!("can't read tonlib log {}: {err}", path.display());
+                return;
+            }
+        };
+        if stopping {
+            return;
+        }
+
+        std::thread::sleep(Duration::from_millis(100));
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            // between tonlib's rename and its new file
+            Err(_) => continue,
+        };
+        // renamed and replaced, or truncated in place
+        let replaced = id.is_some() && id != current_file_id(&metadata);
+        let rotated = replaced || metadata.len() < position;
+        if !rotated {
+            continue;
+        }
+        // the open file still has the lines written before the rename
+        if let Err(err) = forward_lines(&mut reader, position, &mut level) {
+            error!("can't read tonlib log {}: {err}", path.display());
+        }
+        if let Ok(file) = File::open(path) {
+            id = file_id(&file);
+            reader = BufReader::new(file);
+            position = 0;
+        }
+    }
+}
+
+/// Forwards the complete lines after `position`, returns the position after them.
+fn forward_lines(
+    reader: &mut BufReader<File>,
+    mut position: u64,
+    level: &mut Level,
+) -> std::io::Result<u64> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        // a partial line is read again once tonlib finishes it
+        if read == 0 || !line.ends_with('\n') {
+            reader.seek(SeekFrom::Start(position))?;
+            return Ok(position);
+        }
+        position += read as u64;
+        *level = forward_line(line.trim_end(), *level);
+    }
+}
+
+#[cfg(unix)]
+fn current_file_id(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn current_file_id(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn file_id(file: &File) -> Option<(u64, u64)> {
+    current_file_id(&file.metadata().ok()?)
+}
+
+/// Logs one tonlib line, like `[ 2][t 1][2023-01-01 10:00:00.123][Client.cpp:42]\tmessage`.
+///
+/// Lines without a header continue the previous message and keep its level.
+fn forward_line(line: &str, previous: Level) -> Level {
+    let (level, message) = match parse_line(line, previous) {
+        Some(parsed) => parsed,
+        None => return previous,
+    };
+    log::log!(target: TARGET, level, "{message}");
+    return level;
+}
+
+/// Returns the level and message of a tonlib line, `None` for an empty one.
+fn parse_line(line: &str, previous: Level) -> Option<(Level, &str)> {
+    if line.is_empty() {
+        return None;
+    }
+
+    let verbosity = line
+        .strip_prefix('[')
+        .and_then(|rest| rest.split(']').next())
+        .and_then(|verbosity| verbosity.trim().parse::<i32>().ok());
+    let parsed = match verbosity {
+        Some(verbosity) => (
+            level(verbosity),
+            line.split_once('\t').map_or(line, |(_, message)| message),
+        ),
+        None => (previous, line),
+    };
+    return Some(parsed);
+}
+
+/// Maps tonlib verbosity levels (0 fatal, 1 error, 2 warning, 3 info, 4 debug) to `log` levels.
+fn level(verbosity: i32) -> Level {
+    match verbosity {
+        i32::MIN..=1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn maps_verbosity_to_level() {
+        let levels: Vec<Level> = (-1..=6).map(level).collect();
+        assert_eq!(
+            levels,
+            [
+                Level::Error,
+                Level::Error,
+                Level::Error,
+                Level::Warn,
+                Level::Info,
+                Level::Debug,
+                Level::Trace,
+                Level::Trace,
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_tonlib_lines() {
+        assert_eq!(
+            parse_line(
+                "[ 2][t 1][2023-01-01 10:00:00.123][Client.cpp:42]\tconnection lost",
+                Level::Info
+            ),
+            Some((Level::Warn, "connection lost"))
+        );
+        assert_eq!(
+            parse_line("[ 4][t 0][2023-01-01 10:00:00.123]", Level::Info),
+            Some((Level::Debug, "[ 4][t 0][2023-01-01 10:00:00.123]"))
+        );
+        // continuation lines keep the previous level
+        assert_eq!(
+            parse_line("  at frame 1", Level::Error),
+            Some((Level::Error, "  at frame 1"))
+        );
+        assert_eq!(
+            parse_line("[not a level] text", Level::Debug),
+            Some((Level::Debug, "[not a level] text"))
+        );
+        assert_eq!(parse_line("", Level::Warn), None);
+        assert_eq!(forward_line("", Level::Warn), Level::Warn);
+        assert_eq!(forward_line("[ 1][t 0]\tfailed", Level::Info), Level::Error);
+    }
+
+    #[test]
+    fn forwards_lines_after_position() {
+        let path = std::env::temp_dir().join(format!("tonlib-lines-{}.log", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"[ 3][t 0]\tfirst\n[ 1][t 0]\tsecond\npartial")
+            .unwrap();
+
+        let mut reader = BufReader::new(File::open(&path).unwrap());
+        let mut level = Level::Info;
+        let position = forward_lines(&mut reader, 0, &mut level).unwrap();
+        assert_eq!(position, 33);
+        assert_eq!(level, Level::Error);
+
+        file.write_all(b" line\n").unwrap();
+        let position = forward_lines(&mut reader, position, &mut level).unwrap();
+        assert_eq!(position, 46);
+        assert_eq!(level, Level::Error);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn last_release_stops_forwarder_and_removes_file() {
+        let first = forwarder().unwrap();
+        let second = forwarder().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        let path = first.path().to_path_buf();
+        assert!(path.exists());
+
+        let mut detached = false;
+        release(second, || detached = true);
+        assert!(!detached);
+        assert!(path.exists());
+
+        release(first, || detached = true);
+        assert!(detached);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,40 @@
+use crate::Result;
+
+/// Moves tonlib JSON requests and responses between `Client` and tonlib.
+///
+/// `RawClient` talks to the native tonlibjson library, `ScriptedTransport` answers
+/// in-process and is meant for tests.
+pub trait Transport: Send + Sync + 'static {
+    /// Queues an asynchronous request, the answer is delivered through `receive`.
+    fn send(&self, request: &str) -> Result<()>;
+
+    /// Waits a short, implementation defined time for the next message.
+    ///
+    /// Only the receive thread calls this method.
+    fn receive(&self) -> Option<String>;
+
+    /// Runs a request synchronously and returns its answer.
+    fn execute(&self, request: &str) -> Result<Option<String>>;
+
+    /// Called by the receive thread once the client stopped waiting for the answer
+    /// to the request with `extra` because its timeout passed.
+    fn timed_out(&self, _extra: &str) {}
+}
+
+impl<T: Transport> Transport for std::sync::Arc<T> {
+    fn send(&self, request: &str) -> Result<()> {
+        (**self).send(request)
+    }
+
+    fn receive(&self) -> Option<String> {
+        (**self).receive()
+    }
+
+    fn execute(&self, request: &str) -> Result<Option<String>> {
+        (**self).execute(request)
+    }
+
+    fn timed_out(&self, extra: &str) {
+        (**self).timed_out(extra)
+    }
+}
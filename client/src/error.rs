@@ -0,0 +1,91 @@
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The tonlibjson shared library or one of its symbols could not be loaded.
+    #[error("tonlib library load error: {0}")]
+    LibraryLoad(String),
+
+    /// tonlib answered with an `error` object.
+    #[error("tonlib error {code}: {message}")]
+    TonLib { code: i32, message: String },
+
+    /// No response was received within the request timeout.
+    #[error("request timeout")]
+    Timeout,
+
+    /// A request or response could not be (de)serialized.
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The request was cancelled through its `CancelHandle`.
+    #[error("request cancelled")]
+    Cancelled,
+
+    /// A tonlib answer, like a BoC, could not be decoded.
+    #[error("decode error: {0}")]
+    Decode(String),
+
+    /// A file used by the client could not be read or written.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The client was closed before the request completed.
+    #[error("client closed")]
+    Closed,
+
+    /// tonlib `init` kept failing, `source` is the last error.
+    #[error("client init failed after {attempts} attempts: {source}")]
+    Init { attempts: u32, source: Box<Error> },
+
+    /// The caller passed arguments tonlib can't handle.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    /// No session is left to handle the request.
+    #[error("unavailable: {0}")]
+    Unavailable(String),
+}
+
+impl Error {
+    /// Builds an error from a tonlib `{"@type": "error", "code": .., "message": ..}` object.
+    pub fn from_tonlib(value: &serde_json::Value) -> Self {
+        Self::TonLib {
+            code: value["code"].as_i64().unwrap_or_default() as i32,
+            message: value["message"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Returns whether repeating the same request may succeed.
+    ///
+    /// tonlib reports malformed requests and configs with code 400, those fail again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Timeout | Self::Unavailable(_) => true,
+            Self::TonLib { code, .. } => *code != 400,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the error comes from the connection to the lite server rather
+    /// than from the request itself, like a timeout or a network failure.
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            Self::Timeout => true,
+            // ton's notready, timeout and cancelled error codes
+            Self::TonLib {
+                code: 651..=653, ..
+            } => true,
+            Self::TonLib { message, .. } => message.contains("LITE_SERVER_NETWORK"),
+            _ => false,
+        }
+    }
+
+    /// Returns the tonlib error code if this is a tonlib error.
+    pub fn tonlib_code(&self) -> Option<i32> {
+        match self {
+            Self::TonLib { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
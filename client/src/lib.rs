@@ -1,3 +1,8 @@
+#![allow(clippy::needless_return, clippy::module_inception)]
+
 pub mod client;
+mod error;
 pub mod request;
 pub mod types;
+
+pub use error::*;
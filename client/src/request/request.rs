@@ -2,75 +2,650 @@ use serde_json::json;
 
 use crate::types;
 
+/// A tonlib query.
+///
+/// Implement it downstream to call tonlib methods the crate doesn't wrap yet,
+/// `Client::call` sends the query and decodes the answer into `Response`.
 pub trait Request {
+    type Response: serde::de::DeserializeOwned;
+
     fn serialize(&self, extra: &str) -> String;
+
+    /// Whether the serialized query carries secrets and must not be logged.
+    fn is_sensitive(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum KeyStoreType {
+    Directory(String),
+    InMemory,
+}
+
+pub struct Init {
+    pub lite_server_config: String,
+    pub key_store: KeyStoreType,
+    pub blockchain_name: String,
+    pub ignore_cache: bool,
+    pub use_callbacks_for_network: bool,
+}
+
+impl Request for Init {
+    type Response = serde_json::Value;
+
+    fn serialize(&self, extra: &str) -> String {
+        let keystore_type = match &self.key_store {
+            KeyStoreType::Directory(directory) => json!({
+              "@type": "keyStoreTypeDirectory",
+              "directory": directory
+            }),
+            KeyStoreType::InMemory => json!({ "@type": "keyStoreTypeInMemory" }),
+        };
+
+        let query = json!({
+          "@type": "init",
+          "@extra": extra,
+          "options": {
+            "@type": "options",
+            "config": {
+              "@type": "config",
+              "config": self.lite_server_config,
+              "use_callbacks_for_network": self.use_callbacks_for_network,
+              "blockchain_name": self.blockchain_name,
+              "ignore_cache": self.ignore_cache
+            },
+            "keystore_type": keystore_type
+          }
+        });
+        serde_json::to_string(&query).unwrap()
+    }
+}
+
+pub struct AccountState {
+    pub address: String,
+}
+
+impl Request for AccountState {
+    type Response = types::AccountState;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "raw.getAccountState",
+          "@extra": extra,
+          "account_address": {
+            "account_address": self.address
+          }
+        })
+        .to_string()
+    }
+}
+
+pub struct Transactions {
+    pub address: String,
+    pub from_transaction_lt: Option<String>,
+    pub from_transaction_hash: Option<String>,
+}
+
+impl Request for Transactions {
+    type Response = types::Transactions;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "raw.getTransactions",
+          "@extra": extra,
+          "account_address": {
+            "account_address": self.address
+          },
+          "from_transaction_id": {
+              "@type": "internal.transactionId",
+              "lt": self.from_transaction_lt,
+              "hash": self.from_transaction_hash
+          }
+        })
+        .to_string()
+    }
+}
+
+/// Sends a serialized external message.
+pub struct SendMessage {
+    pub body: Vec<u8>,
+}
+
+impl Request for SendMessage {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "raw.sendMessage",
+          "@extra": extra,
+          "body": base64::encode(&self.body)
+        })
+        .to_string()
+    }
+}
+
+/// Sends a serialized external message, answering with its hash.
+pub struct SendMessageReturnHash {
+    pub body: Vec<u8>,
+}
+
+impl Request for SendMessageReturnHash {
+    type Response = types::ExtMessageInfo;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "raw.sendMessageReturnHash",
+          "@extra": extra,
+          "body": base64::encode(&self.body)
+        })
+        .to_string()
+    }
+}
+
+pub struct SmcLoad {
+    pub address: String,
+}
+
+impl Request for SmcLoad {
+    type Response = types::SmcInfo;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "smc.load",
+          "@extra": extra,
+          "account_address": {
+            "account_address": self.address
+          }
+        })
+        .to_string()
+    }
+}
+
+/// A get-method, by name or by its numeric id.
+#[derive(Debug, Clone)]
+pub enum SmcMethodId {
+    Name(String),
+    Number(i32),
+}
+
+pub struct SmcRunGetMethod {
+    pub id: i64,
+    pub method: SmcMethodId,
+    pub stack: Vec<types::TvmStackEntry>,
+}
+
+impl Request for SmcRunGetMethod {
+    type Response = types::SmcRunResult;
+
+    fn serialize(&self, extra: &str) -> String {
+        let method = match &self.method {
+            SmcMethodId::Name(name) => json!({ "@type": "smc.methodIdName", "name": name }),
+            SmcMethodId::Number(number) => {
+                json!({ "@type": "smc.methodIdNumber", "number": number })
+            }
+        };
+
+        json!({
+          "@type": "smc.runGetMethod",
+          "@extra": extra,
+          "id": self.id,
+          "method": method,
+          "stack": self.stack
+        })
+        .to_string()
+    }
+}
+
+pub struct SmcForget {
+    pub id: i64,
+}
+
+impl Request for SmcForget {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "smc.forget",
+          "@extra": extra,
+          "id": self.id
+        })
+        .to_string()
+    }
+}
+
+/// Resolves `name` starting from the resolver contract `account_address`,
+/// the root DNS contract when `None`.
+pub struct DnsResolve {
+    pub account_address: Option<String>,
+    pub name: String,
+    pub category: types::DnsCategory,
+    /// How many next resolvers tonlib may follow.
+    pub ttl: i32,
+}
+
+impl Request for DnsResolve {
+    type Response = types::DnsResolved;
+
+    fn serialize(&self, extra: &str) -> String {
+        let account_address = self
+            .account_address
+            .as_ref()
+            .map(|address| json!({ "account_address": address }));
+
+        json!({
+          "@type": "dns.resolve",
+          "@extra": extra,
+          "account_address": account_address,
+          "name": self.name,
+          "category": base64::encode(self.category.to_bytes()),
+          "ttl": self.ttl
+        })
+        .to_string()
+    }
+}
+
+pub struct CreateNewKey {
+    pub local_password: types::SecureBytes,
+    pub mnemonic_password: types::SecureBytes,
+    pub random_extra_seed: types::SecureBytes,
+}
+
+impl Request for CreateNewKey {
+    type Response = types::Key;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "createNewKey",
+          "@extra": extra,
+          "local_password": self.local_password,
+          "mnemonic_password": self.mnemonic_password,
+          "random_extra_seed": self.random_extra_seed
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+/// Exports a key as its mnemonic words.
+pub struct ExportKey {
+    pub input_key: types::InputKey,
+}
+
+impl Request for ExportKey {
+    type Response = types::ExportedKey;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "exportKey",
+          "@extra": extra,
+          "input_key": self.input_key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct ExportPemKey {
+    pub input_key: types::InputKey,
+    pub key_password: types::SecureBytes,
+}
+
+impl Request for ExportPemKey {
+    type Response = types::ExportedPemKey;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "exportPemKey",
+          "@extra": extra,
+          "input_key": self.input_key,
+          "key_password": self.key_password
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct ExportEncryptedKey {
+    pub input_key: types::InputKey,
+    pub key_password: types::SecureBytes,
+}
+
+impl Request for ExportEncryptedKey {
+    type Response = types::ExportedEncryptedKey;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "exportEncryptedKey",
+          "@extra": extra,
+          "input_key": self.input_key,
+          "key_password": self.key_password
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct ExportUnencryptedKey {
+    pub input_key: types::InputKey,
+}
+
+impl Request for ExportUnencryptedKey {
+    type Response = types::ExportedUnencryptedKey;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "exportUnencryptedKey",
+          "@extra": extra,
+          "input_key": self.input_key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+/// Imports a key from its mnemonic words.
+pub struct ImportKey {
+    pub local_password: types::SecureBytes,
+    pub mnemonic_password: types::SecureBytes,
+    pub exported_key: types::ExportedKey,
+}
+
+impl Request for ImportKey {
+    type Response = types::Key;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "importKey",
+          "@extra": extra,
+          "local_password": self.local_password,
+          "mnemonic_password": self.mnemonic_password,
+          "exported_key": self.exported_key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct ImportPemKey {
+    pub local_password: types::SecureBytes,
+    pub key_password: types::SecureBytes,
+    pub exported_key: types::ExportedPemKey,
+}
+
+impl Request for ImportPemKey {
+    type Response = types::Key;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "importPemKey",
+          "@extra": extra,
+          "local_password": self.local_password,
+          "key_password": self.key_password,
+          "exported_key": self.exported_key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct ImportEncryptedKey {
+    pub local_password: types::SecureBytes,
+    pub key_password: types::SecureBytes,
+    pub exported_encrypted_key: types::ExportedEncryptedKey,
+}
+
+impl Request for ImportEncryptedKey {
+    type Response = types::Key;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "importEncryptedKey",
+          "@extra": extra,
+          "local_password": self.local_password,
+          "key_password": self.key_password,
+          "exported_encrypted_key": self.exported_encrypted_key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct ImportUnencryptedKey {
+    pub local_password: types::SecureBytes,
+    pub exported_unencrypted_key: types::ExportedUnencryptedKey,
+}
+
+impl Request for ImportUnencryptedKey {
+    type Response = types::Key;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "importUnencryptedKey",
+          "@extra": extra,
+          "local_password": self.local_password,
+          "exported_unencrypted_key": self.exported_unencrypted_key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct DeleteKey {
+    pub key: types::Key,
+}
+
+impl Request for DeleteKey {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "deleteKey",
+          "@extra": extra,
+          "key": self.key
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+pub struct DeleteAllKeys {}
+
+impl Request for DeleteAllKeys {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "deleteAllKeys",
+          "@extra": extra
+        })
+        .to_string()
+    }
+}
+
+pub struct ChangeLocalPassword {
+    pub input_key: types::InputKey,
+    pub new_local_password: types::SecureBytes,
+}
+
+impl Request for ChangeLocalPassword {
+    type Response = types::Key;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "changeLocalPassword",
+          "@extra": extra,
+          "input_key": self.input_key,
+          "new_local_password": self.new_local_password
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
+}
+
+/// Computes the address of a wallet deployed with `initial_account_state`,
+/// `revision` 0 picks the latest wallet code revision.
+pub struct GetAccountAddress {
+    pub initial_account_state: types::InitialAccountState,
+    pub revision: i32,
+    pub workchain_id: i32,
+}
+
+impl Request for GetAccountAddress {
+    type Response = types::AccountAddress;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "getAccountAddress",
+          "@extra": extra,
+          "initial_account_state": self.initial_account_state,
+          "revision": self.revision,
+          "workchain_id": self.workchain_id
+        })
+        .to_string()
+    }
+}
+
+/// Prepares a wallet query sending `messages` from `address`.
+///
+/// `initial_account_state` deploys the wallet along the query when it isn't
+/// initialized yet.
+pub struct CreateQuery {
+    pub private_key: types::InputKey,
+    pub address: String,
+    /// Seconds the message stays valid, tonlib's default when 0.
+    pub timeout: i32,
+    pub messages: Vec<types::Message>,
+    pub allow_send_to_uninited: bool,
+    pub initial_account_state: Option<types::InitialAccountState>,
+}
+
+impl Request for CreateQuery {
+    type Response = types::QueryInfo;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "createQuery",
+          "@extra": extra,
+          "private_key": self.private_key,
+          "address": {
+            "account_address": self.address
+          },
+          "timeout": self.timeout,
+          "action": {
+            "@type": "actionMsg",
+            "messages": self.messages.iter().map(message).collect::<Vec<_>>(),
+            "allow_send_to_uninited": self.allow_send_to_uninited
+          },
+          "initial_account_state": self.initial_account_state
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        true
+    }
 }
 
-pub struct Init {
-    pub lite_server_config: String,
-    pub key_store_directory: String,
+pub struct QuerySend {
+    pub id: i64,
 }
 
-impl Request for Init {
+impl Request for QuerySend {
+    type Response = types::Empty;
+
     fn serialize(&self, extra: &str) -> String {
-        let query = json!({
-          "@type": "init",
+        json!({
+          "@type": "query.send",
           "@extra": extra,
-          "options": {
-            "@type": "options",
-            "config": {
-              "@type": "config",
-              "config": self.lite_server_config,
-              "use_callbacks_for_network": false,
-              "blockchain_name": "",
-              "ignore_cache": false
-            },
-            "keystore_type": {
-              "@type": "keyStoreTypeDirectory",
-              "directory": self.key_store_directory
-            }
-          }
-        });
-        serde_json::to_string(&query).unwrap()
+          "id": self.id
+        })
+        .to_string()
     }
 }
 
-pub struct AccountState {
-    pub address: String,
+pub struct QueryEstimateFees {
+    pub id: i64,
+    /// Estimate as if the signature was valid, for queries made with `InputKey::Fake`.
+    pub ignore_chksig: bool,
 }
 
-impl Request for AccountState {
+impl Request for QueryEstimateFees {
+    type Response = types::QueryFees;
+
     fn serialize(&self, extra: &str) -> String {
         json!({
-          "@type": "raw.getAccountState",
+          "@type": "query.estimateFees",
           "@extra": extra,
-          "account_address": {
-            "account_address": self.address
-          }
+          "id": self.id,
+          "ignore_chksig": self.ignore_chksig
         })
         .to_string()
     }
 }
 
-pub struct Transactions {
-    pub address: String,
-    pub from_transaction_lt: Option<String>,
-    pub from_transaction_hash: Option<String>,
+pub struct QueryGetInfo {
+    pub id: i64,
 }
 
-impl Request for Transactions {
+impl Request for QueryGetInfo {
+    type Response = types::QueryInfo;
+
     fn serialize(&self, extra: &str) -> String {
         json!({
-          "@type": "raw.getTransactions",
+          "@type": "query.getInfo",
           "@extra": extra,
-          "account_address": {
-            "account_address": self.address
-          },
-          "from_transaction_id": {
-              "@type": "internal.transactionId",
-              "lt": self.from_transaction_lt,
-              "hash": self.from_transaction_hash
-          }
+          "id": self.id
+        })
+        .to_string()
+    }
+}
+
+pub struct QueryForget {
+    pub id: i64,
+}
+
+impl Request for QueryForget {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "query.forget",
+          "@extra": extra,
+          "id": self.id
         })
         .to_string()
     }
@@ -79,6 +654,8 @@ impl Request for Transactions {
 pub struct MasterChainInfo {}
 
 impl Request for MasterChainInfo {
+    type Response = types::MasterChainInfo;
+
     fn serialize(&self, extra: &str) -> String {
         json!({
           "@type": "blocks.getMasterchainInfo",
@@ -88,9 +665,25 @@ impl Request for MasterChainInfo {
     }
 }
 
+pub struct LiteServerGetInfo {}
+
+impl Request for LiteServerGetInfo {
+    type Response = types::LiteServerInfo;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "liteServer.getInfo",
+          "@extra": extra
+        })
+        .to_string()
+    }
+}
+
 pub struct SyncClient {}
 
 impl Request for SyncClient {
+    type Response = types::BlockId;
+
     fn serialize(&self, extra: &str) -> String {
         json!({
           "@type": "sync",
@@ -100,31 +693,59 @@ impl Request for SyncClient {
     }
 }
 
+pub struct Close {}
+
+impl Request for Close {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "close",
+          "@extra": extra
+        })
+        .to_string()
+    }
+}
+
 pub struct BlockTransactions {
-    pub fullblock: Option<String>,
+    pub id: types::BlockId,
     pub count: usize,
     pub after_tx: Option<types::AccountTransactionId>,
 }
 
 impl Request for BlockTransactions {
+    type Response = types::BlockTransactions;
+
     fn serialize(&self, extra: &str) -> String {
-        let mut mode: usize = 7;
-        let mut after_tx = json!({
-          "@type": "blocks.accountTransactionId",
-          "account": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
-          "lt": 0
-        });
+        let (mode, after_tx) = transactions_cursor(self.after_tx.as_ref());
+        return json!({
+            "@type": "blocks.getTransactions",
+            "@extra": extra,
+            "id": block_id_ext(&self.id),
+            "mode": mode,
+            "count": self.count,
+            "after": after_tx
+        })
+        .to_string();
+    }
+}
 
-        if let Some(account_tx_id) = self.after_tx.as_ref() {
-            mode = 7 + 128;
-            after_tx["account"] = json!(account_tx_id.account);
-            after_tx["lt"] = json!(account_tx_id.lt);
-        }
+/// Like `BlockTransactions`, answering with full transactions.
+pub struct BlockTransactionsExt {
+    pub id: types::BlockId,
+    pub count: usize,
+    pub after_tx: Option<types::AccountTransactionId>,
+}
+
+impl Request for BlockTransactionsExt {
+    type Response = types::BlockTransactionsExt;
 
+    fn serialize(&self, extra: &str) -> String {
+        let (mode, after_tx) = transactions_cursor(self.after_tx.as_ref());
         return json!({
-            "@type": "blocks.transactions",
+            "@type": "blocks.getTransactionsExt",
             "@extra": extra,
-            "id": self.fullblock,
+            "id": block_id_ext(&self.id),
             "mode": mode,
             "count": self.count,
             "after": after_tx
@@ -133,6 +754,124 @@ impl Request for BlockTransactions {
     }
 }
 
+/// Returns the `mode` and `after` fields of a block transactions query.
+fn transactions_cursor(
+    after_tx: Option<&types::AccountTransactionId>,
+) -> (usize, serde_json::Value) {
+    let mut mode: usize = 7;
+    let mut after = json!({
+      "@type": "blocks.accountTransactionId",
+      "account": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+      "lt": 0
+    });
+
+    if let Some(account_tx_id) = after_tx {
+        mode = 7 + 128;
+        after["account"] = json!(account_tx_id.account);
+        after["lt"] = json!(account_tx_id.lt);
+    }
+    return (mode, after);
+}
+
+/// Reads the config parameter `param` of the last known block.
+pub struct GetConfigParam {
+    pub param: u32,
+}
+
+impl Request for GetConfigParam {
+    type Response = types::ConfigInfo;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "getConfigParam",
+          "@extra": extra,
+          "mode": 0,
+          "param": self.param
+        })
+        .to_string()
+    }
+}
+
+/// Reads the whole config dictionary of the last known block.
+pub struct GetConfigAll {}
+
+impl Request for GetConfigAll {
+    type Response = types::ConfigInfo;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "getConfigAll",
+          "@extra": extra,
+          "mode": 0
+        })
+        .to_string()
+    }
+}
+
+/// Runs `request` against the state of block `id` instead of the last known one.
+pub struct WithBlock<R: Request> {
+    pub id: types::BlockId,
+    pub request: R,
+}
+
+impl<R: Request> Request for WithBlock<R> {
+    type Response = R::Response;
+
+    fn serialize(&self, extra: &str) -> String {
+        let mut function: serde_json::Value =
+            serde_json::from_str(&self.request.serialize(extra)).unwrap();
+        if let Some(function) = function.as_object_mut() {
+            function.remove("@extra");
+        }
+
+        json!({
+          "@type": "withBlock",
+          "@extra": extra,
+          "id": block_id_ext(&self.id),
+          "function": function
+        })
+        .to_string()
+    }
+
+    fn is_sensitive(&self) -> bool {
+        self.request.is_sensitive()
+    }
+}
+
+pub struct Shards {
+    pub id: types::BlockId,
+}
+
+impl Request for Shards {
+    type Response = types::Shards;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "blocks.getShards",
+          "@extra": extra,
+          "id": block_id_ext(&self.id)
+        })
+        .to_string()
+    }
+}
+
+pub struct BlockHeader {
+    pub id: types::BlockId,
+}
+
+impl Request for BlockHeader {
+    type Response = types::BlockHeader;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "blocks.getBlockHeader",
+          "@extra": extra,
+          "id": block_id_ext(&self.id)
+        })
+        .to_string()
+    }
+}
+
 pub struct LookupBlock {
     pub workchain: i32,
     pub shard: String,
@@ -142,6 +881,8 @@ pub struct LookupBlock {
 }
 
 impl Request for LookupBlock {
+    type Response = types::BlockId;
+
     fn serialize(&self, extra: &str) -> String {
         let mut mode = 0;
         if self.seqno.is_some() {
@@ -170,3 +911,347 @@ impl Request for LookupBlock {
         .to_string()
     }
 }
+
+pub struct PackAccountAddress {
+    pub workchain_id: i32,
+    pub bounceable: bool,
+    pub testnet: bool,
+    pub addr: String,
+}
+
+impl Request for PackAccountAddress {
+    type Response = types::AccountAddress;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "packAccountAddress",
+          "@extra": extra,
+          "account_address": {
+            "@type": "unpackedAccountAddress",
+            "workchain_id": self.workchain_id,
+            "bounceable": self.bounceable,
+            "testnet": self.testnet,
+            "addr": self.addr
+          }
+        })
+        .to_string()
+    }
+}
+
+pub struct UnpackAccountAddress {
+    pub account_address: String,
+}
+
+impl Request for UnpackAccountAddress {
+    type Response = types::UnpackedAccountAddress;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "unpackAccountAddress",
+          "@extra": extra,
+          "account_address": self.account_address
+        })
+        .to_string()
+    }
+}
+
+pub struct Bip39Hints {
+    pub prefix: String,
+}
+
+impl Request for Bip39Hints {
+    type Response = types::Bip39Hints;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "getBip39Hints",
+          "@extra": extra,
+          "prefix": self.prefix
+        })
+        .to_string()
+    }
+}
+
+pub struct GetLogVerbosityLevel {}
+
+impl Request for GetLogVerbosityLevel {
+    type Response = types::LogVerbosityLevel;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "getLogVerbosityLevel",
+          "@extra": extra
+        })
+        .to_string()
+    }
+}
+
+pub struct SetLogVerbosityLevel {
+    pub new_verbosity_level: i32,
+}
+
+impl Request for SetLogVerbosityLevel {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "setLogVerbosityLevel",
+          "@extra": extra,
+          "new_verbosity_level": self.new_verbosity_level
+        })
+        .to_string()
+    }
+}
+
+pub struct SetLogTagVerbosityLevel {
+    pub tag: String,
+    pub new_verbosity_level: i32,
+}
+
+impl Request for SetLogTagVerbosityLevel {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        json!({
+          "@type": "setLogTagVerbosityLevel",
+          "@extra": extra,
+          "tag": self.tag,
+          "new_verbosity_level": self.new_verbosity_level
+        })
+        .to_string()
+    }
+}
+
+pub enum LogStream {
+    /// tonlib's default stream, stderr.
+    Default,
+    File {
+        path: String,
+        max_file_size: i64,
+    },
+    Empty,
+}
+
+pub struct SetLogStream {
+    pub log_stream: LogStream,
+}
+
+impl Request for SetLogStream {
+    type Response = types::Empty;
+
+    fn serialize(&self, extra: &str) -> String {
+        let log_stream = match &self.log_stream {
+            LogStream::Default => json!({ "@type": "logStreamDefault" }),
+            LogStream::File {
+                path,
+                max_file_size,
+            } => json!({
+              "@type": "logStreamFile",
+              "path": path,
+              "max_file_size": max_file_size
+            }),
+            LogStream::Empty => json!({ "@type": "logStreamEmpty" }),
+        };
+
+        json!({
+          "@type": "setLogStream",
+          "@extra": extra,
+          "log_stream": log_stream
+        })
+        .to_string()
+    }
+}
+
+fn block_id_ext(id: &types::BlockId) -> serde_json::Value {
+    json!({
+      "@type": "ton.blockIdExt",
+      "workchain": id.workchain,
+      "shard": id.shard,
+      "seqno": id.seqno,
+      "root_hash": id.root_hash,
+      "file_hash": id.file_hash
+    })
+}
+
+fn message(message: &types::Message) -> serde_json::Value {
+    let data = match &message.data {
+        types::MessageData::Text(text) => json!({
+          "@type": "msg.dataText",
+          "text": base64::encode(text)
+        }),
+        // tonlib encrypts the decrypted text for the destination
+        types::MessageData::EncryptedText(text) => json!({
+          "@type": "msg.dataDecryptedText",
+          "text": base64::encode(text)
+        }),
+        types::MessageData::Raw { body, init_state } => json!({
+          "@type": "msg.dataRaw",
+          "body": base64::encode(body),
+          "init_state": base64::encode(init_state)
+        }),
+    };
+
+    json!({
+      "@type": "msg.message",
+      "destination": {
+        "account_address": message.destination
+      },
+      "public_key": message.public_key,
+      "amount": message.amount,
+      "data": data,
+      "send_mode": message.send_mode
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialized(request: impl Request) -> serde_json::Value {
+        return serde_json::from_str(&request.serialize("1")).unwrap();
+    }
+
+    #[test]
+    fn serializes_dns_resolve() {
+        assert_eq!(
+            serialized(DnsResolve {
+                account_address: None,
+                name: "foundation.ton".to_string(),
+                category: types::DnsCategory::Wallet,
+                ttl: 0,
+            }),
+            json!({
+              "@type": "dns.resolve",
+              "@extra": "1",
+              "account_address": null,
+              "name": "foundation.ton",
+              "category": "6NRAUIc9uoZap8Fwq0zOZNkIOaNNz9bPcdFOAgVEOxs=",
+              "ttl": 0
+            })
+        );
+        assert_eq!(
+            serialized(DnsResolve {
+                account_address: Some("EQresolver".to_string()),
+                name: "wallet".to_string(),
+                category: types::DnsCategory::All,
+                ttl: 3,
+            }),
+            json!({
+              "@type": "dns.resolve",
+              "@extra": "1",
+              "account_address": { "account_address": "EQresolver" },
+              "name": "wallet",
+              "category": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+              "ttl": 3
+            })
+        );
+    }
+
+    fn input_key() -> types::InputKey {
+        return types::InputKey::new(
+            types::Key {
+                public_key: "PuZ".to_string(),
+                secret: types::SecureBytes::from("secret"),
+            },
+            types::SecureBytes::from("pass"),
+        );
+    }
+
+    #[test]
+    fn serializes_create_query() {
+        let request = CreateQuery {
+            private_key: input_key(),
+            address: "EQwallet".to_string(),
+            timeout: 60,
+            messages: vec![
+                types::Message::new("EQdest", 1_000_000_000).with_comment("hi"),
+                types::Message {
+                    public_key: "PuDest".to_string(),
+                    send_mode: 1,
+                    ..types::Message::new("EQother", 5).with_encrypted_comment("secret note")
+                },
+                types::Message {
+                    data: types::MessageData::Raw {
+                        body: vec![1, 2],
+                        init_state: vec![],
+                    },
+                    ..types::Message::new("EQraw", 7)
+                },
+            ],
+            allow_send_to_uninited: true,
+            initial_account_state: Some(types::InitialAccountState::WalletV3 {
+                public_key: "PuZ".to_string(),
+                wallet_id: 698983191,
+            }),
+        };
+        assert!(request.is_sensitive());
+        assert_eq!(
+            serialized(request),
+            json!({
+              "@type": "createQuery",
+              "@extra": "1",
+              "private_key": {
+                "@type": "inputKeyRegular",
+                "key": { "public_key": "PuZ", "secret": "c2VjcmV0" },
+                "local_password": "cGFzcw=="
+              },
+              "address": { "account_address": "EQwallet" },
+              "timeout": 60,
+              "action": {
+                "@type": "actionMsg",
+                "messages": [
+                  {
+                    "@type": "msg.message",
+                    "destination": { "account_address": "EQdest" },
+                    "public_key": "",
+                    "amount": 1_000_000_000,
+                    "data": { "@type": "msg.dataText", "text": "aGk=" },
+                    "send_mode": 3
+                  },
+                  {
+                    "@type": "msg.message",
+                    "destination": { "account_address": "EQother" },
+                    "public_key": "PuDest",
+                    "amount": 5,
+                    "data": { "@type": "msg.dataDecryptedText", "text": "c2VjcmV0IG5vdGU=" },
+                    "send_mode": 1
+                  },
+                  {
+                    "@type": "msg.message",
+                    "destination": { "account_address": "EQraw" },
+                    "public_key": "",
+                    "amount": 7,
+                    "data": { "@type": "msg.dataRaw", "body": "AQI=", "init_state": "" },
+                    "send_mode": 3
+                  }
+                ],
+                "allow_send_to_uninited": true
+              },
+              "initial_account_state": {
+                "@type": "wallet.v3.initialAccountState",
+                "public_key": "PuZ",
+                "wallet_id": 698983191
+              }
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_create_query_for_deployed_wallet() {
+        let value = serialized(CreateQuery {
+            private_key: types::InputKey::Fake,
+            address: "EQwallet".to_string(),
+            timeout: 0,
+            messages: vec![types::Message::new("EQdest", 1)],
+            allow_send_to_uninited: false,
+            initial_account_state: None,
+        });
+        assert_eq!(value["private_key"], json!({ "@type": "inputKeyFake" }));
+        assert_eq!(value["action"]["allow_send_to_uninited"], json!(false));
+        assert_eq!(
+            value["action"]["messages"][0]["data"],
+            json!({ "@type": "msg.dataText", "text": "" })
+        );
+        assert_eq!(value["initial_account_state"], json!(null));
+    }
+}
@@ -0,0 +1,379 @@
+use std::sync::Arc;
+
+use crate::{Error, Result};
+
+const BOC_MAGIC: u32 = 0xb5ee9c72;
+
+/// A TVM cell, decoded from a bag of cells (BoC).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    data: Vec<u8>,
+    bit_len: usize,
+    references: Vec<Arc<Cell>>,
+}
+
+impl Cell {
+    /// Decodes a serialized bag of cells and returns its first root.
+    pub fn from_boc(boc: &[u8]) -> Result<Arc<Self>> {
+        let mut reader = BocReader { boc, offset: 0 };
+        if reader.uint(4)? as u32 != BOC_MAGIC {
+            return Err(decode_error("unsupported boc magic"));
+        }
+        let flags = reader.uint(1)?;
+        let has_index = flags & 0x80 != 0;
+        let ref_size = (flags & 0x07) as usize;
+        let offset_size = reader.uint(1)? as usize;
+        let cell_count = reader.uint(ref_size)? as usize;
+        let root_count = reader.uint(ref_size)? as usize;
+        let _absent = reader.uint(ref_size)?;
+        let _total_size = reader.uint(offset_size)?;
+        if root_count == 0 {
+            return Err(decode_error("boc without roots"));
+        }
+        let root = reader.uint(ref_size)? as usize;
+        reader.skip((root_count - 1) * ref_size)?;
+        if has_index {
+            reader.skip(cell_count * offset_size)?;
+        }
+
+        let mut raw_cells = Vec::with_capacity(cell_count);
+        for _ in 0..cell_count {
+            let d1 = reader.uint(1)? as usize;
+            let d2 = reader.uint(1)? as usize;
+            let reference_count = d1 & 0x07;
+            if d1 & 0x10 != 0 {
+                // stored hashes and depths, one of each per level plus one
+                let level_mask = d1 >> 5;
+                reader.skip((level_mask.count_ones() as usize + 1) * (32 + 2))?;
+            }
+            let data = reader.bytes(d2.div_ceil(2))?.to_vec();
+            let mut references = Vec::with_capacity(reference_count);
+            for _ in 0..reference_count {
+                references.push(reader.uint(ref_size)? as usize);
+            }
+            let bit_len = match d2 % 2 {
+                0 => data.len() * 8,
+                // the last byte is completed with a 1 bit followed by zeros
+                _ => match data.last() {
+                    Some(last) if *last != 0 => data.len() * 8 - last.trailing_zeros() as usize - 1,
+                    _ => return Err(decode_error("bad cell padding")),
+                },
+            };
+            raw_cells.push((data, bit_len, references));
+        }
+
+        // cells only reference cells serialized after them
+        let mut cells: Vec<Option<Arc<Cell>>> = vec![None; cell_count];
+        for (index, (data, bit_len, references)) in raw_cells.into_iter().enumerate().rev() {
+            let references = references
+                .into_iter()
+                .map(|reference| match cells.get(reference) {
+                    Some(Some(cell)) if reference > index => Ok(cell.clone()),
+                    _ => Err(decode_error("bad cell reference")),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            cells[index] = Some(Arc::new(Cell {
+                data,
+                bit_len,
+                references,
+            }));
+        }
+        return cells
+            .get(root)
+            .cloned()
+            .flatten()
+            .ok_or_else(|| decode_error("bad boc root"));
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+
+    pub fn references(&self) -> &[Arc<Cell>] {
+        &self.references
+    }
+
+    /// Starts reading the cell from its first bit and reference.
+    pub fn parser(&self) -> CellParser<'_> {
+        CellParser {
+            cell: self,
+            bit: 0,
+            reference: 0,
+        }
+    }
+}
+
+/// Sequential reader over the bits and references of a `Cell`.
+#[derive(Debug, Clone)]
+pub struct CellParser<'a> {
+    cell: &'a Cell,
+    bit: usize,
+    reference: usize,
+}
+
+impl<'a> CellParser<'a> {
+    pub fn remaining_bits(&self) -> usize {
+        self.cell.bit_len - self.bit
+    }
+
+    pub fn load_bit(&mut self) -> Result<bool> {
+        if self.remaining_bits() == 0 {
+            return Err(decode_error("cell underflow"));
+        }
+        let byte = self.cell.data[self.bit / 8];
+        let bit = byte >> (7 - self.bit % 8) & 1 == 1;
+        self.bit += 1;
+        return Ok(bit);
+    }
+
+    /// Reads an unsigned big-endian integer of up to 128 bits.
+    pub fn load_uint(&mut self, bits: usize) -> Result<u128> {
+        if bits > 128 {
+            return Err(decode_error("integer wider than 128 bits"));
+        }
+        if bits > self.remaining_bits() {
+            return Err(decode_error("cell underflow"));
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            value = value << 1 | self.load_bit()? as u128;
+        }
+        return Ok(value);
+    }
+
+    pub fn load_u8(&mut self) -> Result<u8> {
+        Ok(self.load_uint(8)? as u8)
+    }
+
+    pub fn load_u16(&mut self) -> Result<u16> {
+        Ok(self.load_uint(16)? as u16)
+    }
+
+    pub fn load_u32(&mut self) -> Result<u32> {
+        Ok(self.load_uint(32)? as u32)
+    }
+
+    pub fn load_i32(&mut self) -> Result<i32> {
+        Ok(self.load_uint(32)? as u32 as i32)
+    }
+
+    pub fn load_u64(&mut self) -> Result<u64> {
+        Ok(self.load_uint(64)? as u64)
+    }
+
+    /// Reads `bytes` whole bytes, not necessarily byte aligned.
+    pub fn load_bytes(&mut self, bytes: usize) -> Result<Vec<u8>> {
+        return (0..bytes).map(|_| self.load_u8()).collect();
+    }
+
+    pub fn load_u256(&mut self) -> Result<[u8; 32]> {
+        let mut value = [0; 32];
+        value.copy_from_slice(&self.load_bytes(32)?);
+        return Ok(value);
+    }
+
+    /// Reads a `Grams` amount, in nanotons.
+    pub fn load_coins(&mut self) -> Result<u128> {
+        let len = self.load_uint(4)? as usize;
+        return self.load_uint(len * 8);
+    }
+
+    pub fn load_reference(&mut self) -> Result<&'a Arc<Cell>> {
+        let cell: &'a Cell = self.cell;
+        let reference = cell
+            .references
+            .get(self.reference)
+            .ok_or_else(|| decode_error("cell has no more references"))?;
+        self.reference += 1;
+        return Ok(reference);
+    }
+
+    /// Reads a `HashmapE n X`: a bit telling whether the map is empty, then a reference
+    /// to its root.
+    pub fn load_dict(&mut self, key_bits: usize) -> Result<Vec<(u128, CellParser<'a>)>> {
+        if !self.load_bit()? {
+            return Ok(Vec::new());
+        }
+        return self.load_reference()?.parser().load_hashmap(key_bits);
+    }
+
+    /// Reads a non-empty `Hashmap n X` rooted in this cell, returning each key with a
+    /// parser positioned at its value.
+    pub fn load_hashmap(self, key_bits: usize) -> Result<Vec<(u128, CellParser<'a>)>> {
+        if key_bits > 128 {
+            return Err(decode_error("hashmap keys wider than 128 bits"));
+        }
+        let mut entries = Vec::new();
+        hashmap_edge(self, key_bits, 0, &mut entries)?;
+        return Ok(entries);
+    }
+
+    fn load_label(&mut self, max_len: usize) -> Result<(u128, usize)> {
+        // bits needed to store a length up to max_len
+        let len_bits = (usize::BITS - max_len.leading_zeros()) as usize;
+        if !self.load_bit()? {
+            // hml_short$0, unary length
+            let mut len = 0;
+            while self.load_bit()? {
+                len += 1;
+            }
+            return Ok((self.load_uint(len)?, len));
+        }
+        if !self.load_bit()? {
+            // hml_long$10
+            let len = self.load_uint(len_bits)? as usize;
+            return Ok((self.load_uint(len)?, len));
+        }
+        // hml_same$11
+        let same = self.load_bit()?;
+        let len = self.load_uint(len_bits)? as usize;
+        let label = match (same, len) {
+            (false, _) | (true, 0) => 0,
+            (true, 128) => u128::MAX,
+            (true, len) => (1 << len) - 1,
+        };
+        return Ok((label, len));
+    }
+}
+
+fn hashmap_edge<'a>(
+    mut parser: CellParser<'a>,
+    key_bits: usize,
+    prefix: u128,
+    entries: &mut Vec<(u128, CellParser<'a>)>,
+) -> Result<()> {
+    let (label, label_len) = parser.load_label(key_bits)?;
+    if label_len > key_bits {
+        return Err(decode_error("hashmap label longer than key"));
+    }
+    let key = match label_len {
+        0 => prefix,
+        128 => label,
+        len => prefix << len | label,
+    };
+    let remaining = key_bits - label_len;
+    if remaining == 0 {
+        entries.push((key, parser));
+        return Ok(());
+    }
+
+    let left = parser.load_reference()?;
+    let right = parser.load_reference()?;
+    hashmap_edge(left.parser(), remaining - 1, key << 1, entries)?;
+    hashmap_edge(right.parser(), remaining - 1, key << 1 | 1, entries)?;
+    return Ok(());
+}
+
+struct BocReader<'a> {
+    boc: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BocReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .boc
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| decode_error("truncated boc"))?;
+        self.offset += len;
+        return Ok(bytes);
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64> {
+        if len > 8 {
+            return Err(decode_error("boc integer wider than 8 bytes"));
+        }
+        let value = self
+            .bytes(len)?
+            .iter()
+            .fold(0, |value, byte| value << 8 | *byte as u64);
+        return Ok(value);
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len).map(|_| ())
+    }
+}
+
+fn decode_error(message: &str) -> Error {
+    Error::Decode(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mainnet config param 8, as returned by `getConfigParam`.
+    const GLOBAL_VERSION_BOC: &str = "te6ccgEBAQEADwAAGsQAAAAHAAAAAAAAAC4=";
+
+    fn decode(boc: &str) -> Arc<Cell> {
+        return Cell::from_boc(&base64::decode(boc).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reads_bits_and_integers() {
+        let cell = decode(GLOBAL_VERSION_BOC);
+        assert_eq!(cell.bit_len(), 104);
+        assert!(cell.references().is_empty());
+
+        let mut parser = cell.parser();
+        assert_eq!(parser.load_u8().unwrap(), 0xc4);
+        assert_eq!(parser.load_u32().unwrap(), 7);
+        assert!(!parser.load_bit().unwrap());
+        assert_eq!(parser.load_uint(63).unwrap(), 46);
+        assert_eq!(parser.remaining_bits(), 0);
+        assert!(matches!(parser.load_bit(), Err(Error::Decode(_))));
+    }
+
+    #[test]
+    fn skips_stored_hashes() {
+        let mut boc = base64::decode(GLOBAL_VERSION_BOC).unwrap();
+        // single cell, its descriptors start right after the 8 byte header and root index
+        let cell_start = 11;
+        boc[cell_start] |= 0x10;
+        boc.splice(cell_start + 2..cell_start + 2, [0xab; 34]);
+        boc[9] += 34;
+
+        let cell = Cell::from_boc(&boc).unwrap();
+        assert_eq!(*cell, *decode(GLOBAL_VERSION_BOC));
+    }
+
+    #[test]
+    fn reads_boc_with_crc() {
+        let boc = include_bytes!("testdata/mainnet_config.boc");
+        assert_eq!(boc[4] & 0xc0, 0x40, "has crc32c, no index");
+        let root = Cell::from_boc(boc).unwrap();
+        assert_eq!(root.references().len(), 2);
+    }
+
+    #[test]
+    fn reads_hashmap_labels() {
+        let root = Cell::from_boc(include_bytes!("testdata/mainnet_config.boc")).unwrap();
+        let keys: Vec<u128> = root
+            .parser()
+            .load_hashmap(32)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys.len(), 35);
+        assert_eq!(&keys[..4], &[0, 1, 2, 4]);
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+        // negative ids, as unsigned keys
+        assert_eq!(keys.last(), Some(&(-71i32 as u32 as u128)));
+    }
+
+    #[test]
+    fn rejects_malformed_boc() {
+        let boc = base64::decode(GLOBAL_VERSION_BOC).unwrap();
+        assert!(matches!(Cell::from_boc(&boc[1..]), Err(Error::Decode(_))));
+        assert!(matches!(
+            Cell::from_boc(&boc[..boc.len() - 1]),
+            Err(Error::Decode(_))
+        ));
+    }
+}
@@ -0,0 +1,487 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{Error, Result};
+
+use super::{Cell, CellParser};
+
+/// tonlib's answer to `getConfigParam` and `getConfigAll`.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ConfigInfo {
+    pub config: TvmCell,
+}
+
+/// A cell as tonlib sends it, a base64 BoC.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct TvmCell {
+    pub bytes: String,
+}
+
+impl TvmCell {
+    pub fn to_cell(&self) -> Result<Arc<Cell>> {
+        let boc = base64::decode(&self.bytes).map_err(|e| Error::Decode(e.to_string()))?;
+        return Cell::from_boc(&boc);
+    }
+}
+
+/// A blockchain config parameter.
+///
+/// Parameters without a typed representation are kept as `Raw` cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigParam {
+    /// 0, address of the config smart contract in the masterchain.
+    ConfigAddress(String),
+    /// 1, address of the elector smart contract in the masterchain.
+    ElectorAddress(String),
+    /// 2, address of the minter smart contract in the masterchain.
+    MinterAddress(String),
+    /// 8.
+    GlobalVersion(GlobalVersion),
+    /// 12, keyed by workchain id.
+    Workchains(BTreeMap<i32, WorkchainDescr>),
+    /// 15.
+    ElectionTimings(ElectionTimings),
+    /// 17.
+    StakeLimits(StakeLimits),
+    /// 18, ordered by `utime_since`.
+    StoragePrices(Vec<StoragePrices>),
+    /// 20.
+    MasterchainGasPrices(GasLimitsPrices),
+    /// 21.
+    GasPrices(GasLimitsPrices),
+    /// 24.
+    MasterchainMsgForwardPrices(MsgForwardPrices),
+    /// 25.
+    MsgForwardPrices(MsgForwardPrices),
+    Raw {
+        id: u32,
+        cell: Arc<Cell>,
+    },
+}
+
+impl ConfigParam {
+    /// Decodes the value cell of parameter `id`.
+    pub fn decode(id: u32, cell: Arc<Cell>) -> Result<Self> {
+        let mut parser = cell.parser();
+        let param = match id {
+            0 => Self::ConfigAddress(masterchain_address(&mut parser)?),
+            1 => Self::ElectorAddress(masterchain_address(&mut parser)?),
+            2 => Self::MinterAddress(masterchain_address(&mut parser)?),
+            8 => Self::GlobalVersion(GlobalVersion::decode(&mut parser)?),
+            12 => {
+                let mut workchains = BTreeMap::new();
+                for (workchain, mut value) in parser.load_dict(32)? {
+                    let workchain = workchain as u32 as i32;
+                    workchains.insert(workchain, WorkchainDescr::decode(workchain, &mut value)?);
+                }
+                Self::Workchains(workchains)
+            }
+            15 => Self::ElectionTimings(ElectionTimings::decode(&mut parser)?),
+            17 => Self::StakeLimits(StakeLimits::decode(&mut parser)?),
+            18 => {
+                let prices = parser
+                    .load_hashmap(32)?
+                    .into_iter()
+                    .map(|(_, mut value)| StoragePrices::decode(&mut value))
+                    .collect::<Result<Vec<_>>>()?;
+                Self::StoragePrices(prices)
+            }
+            20 => Self::MasterchainGasPrices(GasLimitsPrices::decode(&mut parser)?),
+            21 => Self::GasPrices(GasLimitsPrices::decode(&mut parser)?),
+            24 => Self::MasterchainMsgForwardPrices(MsgForwardPrices::decode(&mut parser)?),
+            25 => Self::MsgForwardPrices(MsgForwardPrices::decode(&mut parser)?),
+            id => Self::Raw { id, cell },
+        };
+        return Ok(param);
+    }
+
+    /// Decodes every parameter of the config dictionary returned by `getConfigAll`,
+    /// parameters failing to decode are returned as `Raw`.
+    pub fn decode_all(root: &Cell) -> Result<BTreeMap<u32, Self>> {
+        let mut params = BTreeMap::new();
+        for (id, mut value) in root.parser().load_hashmap(32)? {
+            let id = id as u32;
+            let cell = value.load_reference()?.clone();
+            // one unexpected layout shouldn't hide every other parameter
+            let param = Self::decode(id, cell.clone()).unwrap_or_else(|err| {
+                log::warn!("config param {id} kept raw: {err}");
+                Self::Raw { id, cell }
+            });
+            params.insert(id, param);
+        }
+        return Ok(params);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalVersion {
+    pub version: u32,
+    pub capabilities: u64,
+}
+
+impl GlobalVersion {
+    fn decode(parser: &mut CellParser) -> Result<Self> {
+        expect_tag(parser, &[0xc4], "GlobalVersion")?;
+        Ok(Self {
+            version: parser.load_u32()?,
+            capabilities: parser.load_u64()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkchainDescr {
+    pub workchain: i32,
+    pub enabled_since: u32,
+    pub actual_min_split: u8,
+    pub min_split: u8,
+    pub max_split: u8,
+    pub basic: bool,
+    pub active: bool,
+    pub accept_msgs: bool,
+    pub zerostate_root_hash: String,
+    pub zerostate_file_hash: String,
+    pub version: u32,
+}
+
+impl WorkchainDescr {
+    fn decode(workchain: i32, parser: &mut CellParser) -> Result<Self> {
+        // workchain#a6, workchain_v2#a7 only adds fields after these
+        expect_tag(parser, &[0xa6, 0xa7], "WorkchainDescr")?;
+        let enabled_since = parser.load_u32()?;
+        let actual_min_split = parser.load_u8()?;
+        let min_split = parser.load_u8()?;
+        let max_split = parser.load_u8()?;
+        let basic = parser.load_bit()?;
+        let active = parser.load_bit()?;
+        let accept_msgs = parser.load_bit()?;
+        let _flags = parser.load_uint(13)?;
+        Ok(Self {
+            workchain,
+            enabled_since,
+            actual_min_split,
+            min_split,
+            max_split,
+            basic,
+            active,
+            accept_msgs,
+            zerostate_root_hash: base64::encode(parser.load_u256()?),
+            zerostate_file_hash: base64::encode(parser.load_u256()?),
+            version: parser.load_u32()?,
+        })
+    }
+}
+
+/// Validator election timings, in seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectionTimings {
+    pub validators_elected_for: u32,
+    pub elections_start_before: u32,
+    pub elections_end_before: u32,
+    pub stake_held_for: u32,
+}
+
+impl ElectionTimings {
+    fn decode(parser: &mut CellParser) -> Result<Self> {
+        Ok(Self {
+            validators_elected_for: parser.load_u32()?,
+            elections_start_before: parser.load_u32()?,
+            elections_end_before: parser.load_u32()?,
+            stake_held_for: parser.load_u32()?,
+        })
+    }
+}
+
+/// Validator stake limits, amounts in nanotons.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakeLimits {
+    pub min_stake: u128,
+    pub max_stake: u128,
+    pub min_total_stake: u128,
+    /// Fixed point with 16 fractional bits.
+    pub max_stake_factor: u32,
+}
+
+impl StakeLimits {
+    fn decode(parser: &mut CellParser) -> Result<Self> {
+        Ok(Self {
+            min_stake: parser.load_coins()?,
+            max_stake: parser.load_coins()?,
+            min_total_stake: parser.load_coins()?,
+            max_stake_factor: parser.load_u32()?,
+        })
+    }
+}
+
+/// Storage prices in nanotons per 65536 seconds, valid from `utime_since`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePrices {
+    pub utime_since: u32,
+    pub bit_price_ps: u64,
+    pub cell_price_ps: u64,
+    pub mc_bit_price_ps: u64,
+    pub mc_cell_price_ps: u64,
+}
+
+impl StoragePrices {
+    fn decode(parser: &mut CellParser) -> Result<Self> {
+        expect_tag(parser, &[0xcc], "StoragePrices")?;
+        Ok(Self {
+            utime_since: parser.load_u32()?,
+            bit_price_ps: parser.load_u64()?,
+            cell_price_ps: parser.load_u64()?,
+            mc_bit_price_ps: parser.load_u64()?,
+            mc_cell_price_ps: parser.load_u64()?,
+        })
+    }
+}
+
+/// Gas prices, `gas_price` is in nanotons per 65536 gas units.
+///
+/// `flat_gas_limit` and `flat_gas_price` are zero without a flat price prefix,
+/// `special_gas_limit` equals `gas_limit` in the old `gas_prices#dd` layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasLimitsPrices {
+    pub flat_gas_limit: u64,
+    pub flat_gas_price: u64,
+    pub gas_price: u64,
+    pub gas_limit: u64,
+    pub special_gas_limit: u64,
+    pub gas_credit: u64,
+    pub block_gas_limit: u64,
+    pub freeze_due_limit: u64,
+    pub delete_due_limit: u64,
+}
+
+impl GasLimitsPrices {
+    fn decode(parser: &mut CellParser) -> Result<Self> {
+        let tag = expect_tag(parser, &[0xd1, 0xdd, 0xde], "GasLimitsPrices")?;
+        if tag == 0xd1 {
+            let flat_gas_limit = parser.load_u64()?;
+            let flat_gas_price = parser.load_u64()?;
+            let prices = Self::decode(parser)?;
+            return Ok(Self {
+                flat_gas_limit,
+                flat_gas_price,
+                ..prices
+            });
+        }
+
+        let gas_price = parser.load_u64()?;
+        let gas_limit = parser.load_u64()?;
+        let special_gas_limit = match tag {
+            0xde => parser.load_u64()?,
+            _ => gas_limit,
+        };
+        return Ok(Self {
+            flat_gas_limit: 0,
+            flat_gas_price: 0,
+            gas_price,
+            gas_limit,
+            special_gas_limit,
+            gas_credit: parser.load_u64()?,
+            block_gas_limit: parser.load_u64()?,
+            freeze_due_limit: parser.load_u64()?,
+            delete_due_limit: parser.load_u64()?,
+        });
+    }
+}
+
+/// Message forwarding prices, `first_frac` and `next_frac` are fractions of 65536.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgForwardPrices {
+    pub lump_price: u64,
+    pub bit_price: u64,
+    pub cell_price: u64,
+    pub ihr_price_factor: u32,
+    pub first_frac: u16,
+    pub next_frac: u16,
+}
+
+impl MsgForwardPrices {
+    fn decode(parser: &mut CellParser) -> Result<Self> {
+        expect_tag(parser, &[0xea], "MsgForwardPrices")?;
+        Ok(Self {
+            lump_price: parser.load_u64()?,
+            bit_price: parser.load_u64()?,
+            cell_price: parser.load_u64()?,
+            ihr_price_factor: parser.load_u32()?,
+            first_frac: parser.load_u16()?,
+            next_frac: parser.load_u16()?,
+        })
+    }
+}
+
+fn masterchain_address(parser: &mut CellParser) -> Result<String> {
+    Ok(format!("-1:{}", hex::encode(parser.load_u256()?)))
+}
+
+fn expect_tag(parser: &mut CellParser, tags: &[u8], name: &str) -> Result<u8> {
+    let tag = parser.load_u8()?;
+    if !tags.contains(&tag) {
+        return Err(Error::Decode(format!("unexpected {name} tag {tag:#x}")));
+    }
+    return Ok(tag);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mainnet config parameters, as returned by `getConfigParam`.
+    const PARAM_8: &str = "te6ccgEBAQEADwAAGsQAAAAHAAAAAAAAAC4=";
+    const PARAM_12: &str = "te6ccgEBAgEAYgABAcABALfQUy7nTs8AAAJwACrYn7aHDoYaZOELB7fIx0lsFfzu58bxcmSlH++c6KojdwX2/yWZOw/Zr08OxAx1OQZWjQc9ppdrOeJEc5dIgaEAAAAAD/////gAAAAAAAAABA==";
+    const PARAM_18: &str =
+        "te6ccgEBAQEAKQAATdBmAAAAAAAAAAAAAAAAgAAAAAAAAPoAAAAAAAAB9AAAAAAAA9CQQA==";
+    const PARAM_20: &str = "te6ccgEBAQEATAAAlNEAAAAAAAAAZAAAAAAAD0JA3gAAAAAnEAAAAAAAAAAPQkAAAAAAAhYOwAAAAAAAACcQAAAAAAAmJaAAAAAABfXhAAAAAAA7msoA";
+    const PARAM_21: &str = "te6ccgEBAQEATAAAlNEAAAAAAAAAZAAAAAAAAJxA3gAAAAABkAAAAAAAAAAPQkAAAAAAAA9CQAAAAAAAACcQAAAAAACYloAAAAAABfXhAAAAAAA7msoA";
+    const PARAM_24: &str = "te6ccgEBAQEAIwAAQuoAAAAAAJiWgAAAAAAnEAAAAAAAD0JAAAAAAYAAVVVVVQ==";
+    const PARAM_25: &str = "te6ccgEBAQEAIwAAQuoAAAAAAAYagAAAAAABkAAAAAAAAJxAAAAAAYAAVVVVVQ==";
+
+    fn decode(id: u32, boc: &str) -> ConfigParam {
+        let cell = TvmCell {
+            bytes: boc.to_string(),
+        }
+        .to_cell()
+        .unwrap();
+        return ConfigParam::decode(id, cell).unwrap();
+    }
+
+    #[test]
+    fn decodes_global_version() {
+        assert_eq!(
+            decode(8, PARAM_8),
+            ConfigParam::GlobalVersion(GlobalVersion {
+                version: 7,
+                capabilities: 46,
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_workchains() {
+        let workchains = match decode(12, PARAM_12) {
+            ConfigParam::Workchains(workchains) => workchains,
+            param => panic!("unexpected {param:?}"),
+        };
+        assert_eq!(workchains.len(), 1);
+        let basechain = &workchains[&0];
+        assert_eq!(basechain.enabled_since, 1573821854);
+        assert_eq!(basechain.max_split, 4);
+        assert!(basechain.basic && basechain.active && basechain.accept_msgs);
+        assert_eq!(
+            basechain.zerostate_root_hash,
+            "VbE/bQ4dDDTJwhYPb5GOktgr+d3PjeLkyUo/3znRVEY="
+        );
+        assert_eq!(
+            basechain.zerostate_file_hash,
+            "7gvt/ksydh+zXp4diBjqcgytGg57TS7Wc8SI5y6RA0I="
+        );
+    }
+
+    #[test]
+    fn decodes_storage_prices() {
+        assert_eq!(
+            decode(18, PARAM_18),
+            ConfigParam::StoragePrices(vec![StoragePrices {
+                utime_since: 0,
+                bit_price_ps: 1,
+                cell_price_ps: 500,
+                mc_bit_price_ps: 1000,
+                mc_cell_price_ps: 500000,
+            }])
+        );
+    }
+
+    #[test]
+    fn decodes_gas_prices() {
+        assert_eq!(
+            decode(20, PARAM_20),
+            ConfigParam::MasterchainGasPrices(GasLimitsPrices {
+                flat_gas_limit: 100,
+                flat_gas_price: 1000000,
+                gas_price: 655360000,
+                gas_limit: 1000000,
+                special_gas_limit: 35000000,
+                gas_credit: 10000,
+                block_gas_limit: 2500000,
+                freeze_due_limit: 100000000,
+                delete_due_limit: 1000000000,
+            })
+        );
+        assert_eq!(
+            decode(21, PARAM_21),
+            ConfigParam::GasPrices(GasLimitsPrices {
+                flat_gas_limit: 100,
+                flat_gas_price: 40000,
+                gas_price: 26214400,
+                gas_limit: 1000000,
+                special_gas_limit: 1000000,
+                gas_credit: 10000,
+                block_gas_limit: 10000000,
+                freeze_due_limit: 100000000,
+                delete_due_limit: 1000000000,
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_msg_forward_prices() {
+        assert_eq!(
+            decode(24, PARAM_24),
+            ConfigParam::MasterchainMsgForwardPrices(MsgForwardPrices {
+                lump_price: 10000000,
+                bit_price: 655360000,
+                cell_price: 65536000000,
+                ihr_price_factor: 98304,
+                first_frac: 21845,
+                next_frac: 21845,
+            })
+        );
+        assert_eq!(
+            decode(25, PARAM_25),
+            ConfigParam::MsgForwardPrices(MsgForwardPrices {
+                lump_price: 400000,
+                bit_price: 26214400,
+                cell_price: 2621440000,
+                ihr_price_factor: 98304,
+                first_frac: 21845,
+                next_frac: 21845,
+            })
+        );
+    }
+
+    #[test]
+    fn decodes_full_config() {
+        let root = Cell::from_boc(include_bytes!("testdata/mainnet_config.boc")).unwrap();
+        let params = ConfigParam::decode_all(&root).unwrap();
+        assert_eq!(params.len(), 35);
+        assert_eq!(
+            params[&1],
+            ConfigParam::ElectorAddress(format!("-1:{}", "33".repeat(32)))
+        );
+        assert_eq!(params[&8], decode(8, PARAM_8));
+        assert_eq!(params[&12], decode(12, PARAM_12));
+        assert_eq!(params[&25], decode(25, PARAM_25));
+        assert!(matches!(
+            params[&17],
+            ConfigParam::StakeLimits(StakeLimits {
+                min_stake: 300000000000000,
+                ..
+            })
+        ));
+        assert!(matches!(params[&34], ConfigParam::Raw { id: 34, .. }));
+    }
+
+    #[test]
+    fn keeps_undecodable_param_raw() {
+        // {8: a cell with a wrong GlobalVersion tag}
+        let boc = [
+            0xb5, 0xee, 0x9c, 0x72, 0x01, 0x01, 0x02, 0x01, 0x00, 0x0b, 0x00, // header
+            0x01, 0x0a, 0xa0, 0x00, 0x00, 0x00, 0x08, 0x01, // hml_long label, key 8
+            0x00, 0x02, 0xff, // value
+        ];
+        let root = Cell::from_boc(&boc).unwrap();
+        let params = ConfigParam::decode_all(&root).unwrap();
+        assert!(matches!(params[&8], ConfigParam::Raw { id: 8, .. }));
+    }
+}
@@ -0,0 +1,164 @@
+use super::AccountAddress;
+
+/// A TON DNS record category, identified on chain by the sha256 of its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsCategory {
+    /// Every record of the name.
+    All,
+    Wallet,
+    Site,
+    Storage,
+    NextResolver,
+    Other([u8; 32]),
+}
+
+impl DnsCategory {
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let hash = match self {
+            Self::All => return [0; 32],
+            Self::Other(hash) => return *hash,
+            // sha256("wallet")
+            Self::Wallet => "e8d44050873dba865aa7c170ab4cce64d90839a34dcfd6cf71d14e0205443b1b",
+            // sha256("site")
+            Self::Site => "fbae041b02c41ed0fd8a4efb039bc780dd6af4a1f0c420f42561ae705dda43fe",
+            // sha256("storage")
+            Self::Storage => "49a25f9feefaffecad0fcd30c50dc9331cff8b55ece53def6285c09e17e6f5d7",
+            // sha256("dns_next_resolver")
+            Self::NextResolver => {
+                "19f02441ee588fdb26ee24b2568dd035c3c9206e11ab979be62e55558a1d17ff"
+            }
+        };
+        let mut bytes = [0; 32];
+        hex::decode_to_slice(hash, &mut bytes).unwrap();
+        return bytes;
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct DnsResolved {
+    pub entries: Vec<DnsEntry>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct DnsEntry {
+    pub name: String,
+    /// Base64 category hash.
+    pub category: String,
+    pub entry: DnsEntryData,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AdnlAddress {
+    pub adnl_address: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "@type")]
+pub enum DnsEntryData {
+    #[serde(rename = "dns.entryDataSmcAddress")]
+    SmcAddress { smc_address: AccountAddress },
+    #[serde(rename = "dns.entryDataAdnlAddress")]
+    AdnlAddress { adnl_address: AdnlAddress },
+    /// Base64 id of a TON Storage bag.
+    #[serde(rename = "dns.entryDataStorageAddress")]
+    StorageAddress { bag_id: String },
+    #[serde(rename = "dns.entryDataNextResolver")]
+    NextResolver { resolver: AccountAddress },
+    #[serde(rename = "dns.entryDataText")]
+    Text { text: String },
+    /// A record tonlib can't decode, as a base64 BoC.
+    #[serde(rename = "dns.entryDataUnknown")]
+    Raw { bytes: String },
+    /// A record kind added to tonlib after this client.
+    #[serde(other)]
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entry(data: serde_json::Value) -> DnsEntryData {
+        return serde_json::from_value(data).unwrap();
+    }
+
+    #[test]
+    fn category_hashes_names() {
+        assert_eq!(DnsCategory::All.to_bytes(), [0; 32]);
+        assert_eq!(DnsCategory::Other([7; 32]).to_bytes(), [7; 32]);
+        assert_eq!(
+            hex::encode(DnsCategory::Site.to_bytes()),
+            "fbae041b02c41ed0fd8a4efb039bc780dd6af4a1f0c420f42561ae705dda43fe"
+        );
+    }
+
+    #[test]
+    fn decodes_every_entry_kind() {
+        let smc = entry(json!({
+          "@type": "dns.entryDataSmcAddress",
+          "smc_address": { "@type": "accountAddress", "account_address": "EQwallet" }
+        }));
+        assert!(
+            matches!(smc, DnsEntryData::SmcAddress { smc_address } if smc_address.account_address == "EQwallet")
+        );
+
+        let adnl = entry(json!({
+          "@type": "dns.entryDataAdnlAddress",
+          "adnl_address": { "@type": "adnlAddress", "adnl_address": "vcqmha5j3ceve35ammfrhqty46rkhi455otydstv66pk2tmf7rl25f3" }
+        }));
+        assert!(
+            matches!(adnl, DnsEntryData::AdnlAddress { adnl_address } if adnl_address.adnl_address.starts_with("vcqm"))
+        );
+
+        let storage = entry(json!({
+          "@type": "dns.entryDataStorageAddress",
+          "bag_id": "Sh8ZFoYMLRQqk8Hk3rVHwIb8dQw0S6aJhaJX+CQrpV8="
+        }));
+        assert!(
+            matches!(storage, DnsEntryData::StorageAddress { bag_id } if bag_id.starts_with("Sh8Z"))
+        );
+
+        let next = entry(json!({
+          "@type": "dns.entryDataNextResolver",
+          "resolver": { "@type": "accountAddress", "account_address": "EQresolver" }
+        }));
+        assert!(
+            matches!(next, DnsEntryData::NextResolver { resolver } if resolver.account_address == "EQresolver")
+        );
+
+        let text = entry(json!({ "@type": "dns.entryDataText", "text": "hello" }));
+        assert!(matches!(text, DnsEntryData::Text { text } if text == "hello"));
+
+        let raw = entry(json!({ "@type": "dns.entryDataUnknown", "bytes": "te6ccgE=" }));
+        assert!(matches!(raw, DnsEntryData::Raw { bytes } if bytes == "te6ccgE="));
+    }
+
+    #[test]
+    fn keeps_resolving_with_unknown_entry_kinds() {
+        let resolved: DnsResolved = serde_json::from_value(json!({
+          "@type": "dns.resolved",
+          "entries": [
+            {
+              "@type": "dns.entry",
+              "name": "foundation.ton",
+              "category": "6NRAUIc9uoZap8Fwq0zOZNkIOaNNz9bPcdFOAgVEOxs=",
+              "entry": { "@type": "dns.entryDataFutureKind", "value": 1 }
+            },
+            {
+              "@type": "dns.entry",
+              "name": "foundation.ton",
+              "category": "6NRAUIc9uoZap8Fwq0zOZNkIOaNNz9bPcdFOAgVEOxs=",
+              "entry": { "@type": "dns.entryDataText", "text": "hello" }
+            }
+          ]
+        }))
+        .unwrap();
+        assert!(matches!(resolved.entries[0].entry, DnsEntryData::Unknown));
+        assert!(matches!(
+            resolved.entries[1].entry,
+            DnsEntryData::Text { .. }
+        ));
+    }
+}
@@ -0,0 +1,291 @@
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Secret bytes, wiped from memory on drop. tonlib sends them as base64.
+///
+/// The client also wipes the JSON it exchanges with tonlib, but can't reach
+/// tonlib's own copies or serde_json's parsing buffers.
+#[derive(Clone, Default, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct SecureBytes(Vec<u8>);
+
+impl SecureBytes {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for SecureBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&str> for SecureBytes {
+    fn from(password: &str) -> Self {
+        Self(password.as_bytes().to_vec())
+    }
+}
+
+impl std::fmt::Debug for SecureBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecureBytes(***)")
+    }
+}
+
+impl serde::Serialize for SecureBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(&self.0))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SecureBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut encoded = String::deserialize(deserializer)?;
+        let decoded = base64::decode(&encoded).map_err(serde::de::Error::custom);
+        encoded.zeroize();
+        return decoded.map(Self);
+    }
+}
+
+/// A secret string, like a mnemonic word or a PEM key, wiped from memory on drop.
+#[derive(
+    Clone, Default, PartialEq, Eq, Zeroize, ZeroizeOnDrop, serde::Deserialize, serde::Serialize,
+)]
+#[serde(transparent)]
+pub struct SecureString(String);
+
+impl SecureString {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for SecureString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl std::fmt::Debug for SecureString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecureString(***)")
+    }
+}
+
+/// A key of the tonlib keystore, `secret` is the keystore's handle to the private key.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Key {
+    pub public_key: String,
+    pub secret: SecureBytes,
+}
+
+/// A keystore key unlocked with its local password.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "@type")]
+pub enum InputKey {
+    #[serde(rename = "inputKeyRegular")]
+    Regular {
+        key: Key,
+        local_password: SecureBytes,
+    },
+    /// A placeholder key, only good to estimate fees.
+    #[serde(rename = "inputKeyFake")]
+    Fake,
+}
+
+impl InputKey {
+    pub fn new(key: Key, local_password: SecureBytes) -> Self {
+        Self::Regular {
+            key,
+            local_password,
+        }
+    }
+}
+
+/// A key as its mnemonic words.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ExportedKey {
+    pub word_list: Vec<SecureString>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ExportedPemKey {
+    pub pem: SecureString,
+}
+
+/// A private key encrypted with a key password.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ExportedEncryptedKey {
+    pub data: SecureBytes,
+}
+
+/// A raw private key.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ExportedUnencryptedKey {
+    pub data: SecureBytes,
+}
+
+/// Returns whether a tonlib answer carries key material and must not be logged.
+pub(crate) fn is_sensitive(value: &serde_json::Value) -> bool {
+    const SENSITIVE_TYPES: &[&str] = &[
+        "key",
+        "exportedKey",
+        "exportedPemKey",
+        "exportedEncryptedKey",
+        "exportedUnencryptedKey",
+    ];
+    return SENSITIVE_TYPES.contains(&value["@type"].as_str().unwrap_or_default());
+}
+
+/// Fields holding key material in keystore requests and answers.
+const SECRET_FIELDS: &[&str] = &[
+    "secret",
+    "local_password",
+    "new_local_password",
+    "mnemonic_password",
+    "random_extra_seed",
+    "key_password",
+    "word_list",
+    "pem",
+    "exported_key",
+    "exported_encrypted_key",
+    "exported_unencrypted_key",
+];
+
+/// Empties every secret string of a tonlib request or answer, keeping its shape so
+/// it still deserializes.
+pub(crate) fn redact_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            // exportedEncryptedKey and exportedUnencryptedKey keep the key in `data`
+            let exported = object
+                .get("@type")
+                .and_then(|value| value.as_str())
+                .is_some_and(|value| value.starts_with("exported"));
+            for (field, value) in object.iter_mut() {
+                match SECRET_FIELDS.contains(&field.as_str()) || (exported && field == "data") {
+                    true => blank(value),
+                    false => redact_secrets(value),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+fn blank(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(value) => value.clear(),
+        serde_json::Value::Array(values) => values.iter_mut().for_each(blank),
+        serde_json::Value::Object(object) => object
+            .iter_mut()
+            .filter(|(field, _)| !field.starts_with('@'))
+            .for_each(|(_, value)| blank(value)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn detects_sensitive_answers() {
+        for answer_type in [
+            "key",
+            "exportedKey",
+            "exportedPemKey",
+            "exportedEncryptedKey",
+            "exportedUnencryptedKey",
+        ] {
+            assert!(
+                is_sensitive(&json!({ "@type": answer_type })),
+                "{answer_type}"
+            );
+        }
+        assert!(!is_sensitive(&json!({ "@type": "ok" })));
+        assert!(!is_sensitive(
+            &json!({ "@type": "error", "message": "key" })
+        ));
+        assert!(!is_sensitive(&json!({ "key": {} })));
+        assert!(!is_sensitive(&json!("key")));
+    }
+
+    #[test]
+    fn redacts_secrets_keeping_shape() {
+        let mut request = json!({
+          "@type": "changeLocalPassword",
+          "@extra": "1",
+          "input_key": {
+            "@type": "inputKeyRegular",
+            "key": { "public_key": "PuZ", "secret": "c2VjcmV0" },
+            "local_password": "cGFzcw=="
+          },
+          "new_local_password": "bmV3",
+          "keys": [{ "public_key": "PuX", "secret": "eA==" }]
+        });
+        redact_secrets(&mut request);
+        assert_eq!(
+            request,
+            json!({
+              "@type": "changeLocalPassword",
+              "@extra": "1",
+              "input_key": {
+                "@type": "inputKeyRegular",
+                "key": { "public_key": "PuZ", "secret": "" },
+                "local_password": ""
+              },
+              "new_local_password": "",
+              "keys": [{ "public_key": "PuX", "secret": "" }]
+            })
+        );
+
+        let mut answer = json!({
+          "@type": "exportedKey",
+          "word_list": ["abandon", "ability"],
+          "@extra": "2"
+        });
+        redact_secrets(&mut answer);
+        assert_eq!(
+            answer,
+            json!({ "@type": "exportedKey", "word_list": ["", ""], "@extra": "2" })
+        );
+        let redacted: ExportedKey = serde_json::from_value(answer).unwrap();
+        assert_eq!(redacted.word_list.len(), 2);
+
+        let mut answer = json!({ "@type": "exportedEncryptedKey", "data": "ZGF0YQ==" });
+        redact_secrets(&mut answer);
+        assert_eq!(
+            answer,
+            json!({ "@type": "exportedEncryptedKey", "data": "" })
+        );
+
+        // `data` is only secret in exported keys
+        let mut answer = json!({ "@type": "raw.message", "data": "te6ccgE=" });
+        redact_secrets(&mut answer);
+        assert_eq!(
+            answer,
+            json!({ "@type": "raw.message", "data": "te6ccgE=" })
+        );
+    }
+
+    #[test]
+    fn secure_bytes_round_trip_as_base64() {
+        let secret = SecureBytes::from("secret");
+        assert_eq!(format!("{secret:?}"), "SecureBytes(***)");
+        let encoded = serde_json::to_value(&secret).unwrap();
+        assert_eq!(encoded, json!("c2VjcmV0"));
+        let decoded: SecureBytes = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded.as_bytes(), b"secret");
+    }
+}
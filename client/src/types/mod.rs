@@ -1,4 +1,26 @@
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+mod cell;
+mod config;
+mod dns;
+mod key;
+mod tvm;
+mod wallet;
+
+pub use cell::*;
+pub use config::*;
+pub use dns::*;
+pub use key::*;
+pub use num_bigint::BigInt;
+pub use tvm::*;
+pub use wallet::*;
+
+/// Workchain of the masterchain.
+pub const MASTERCHAIN_WORKCHAIN: i32 = -1;
+/// Workchain of regular accounts.
+pub const BASECHAIN_WORKCHAIN: i32 = 0;
+/// The masterchain's only shard.
+pub const MASTERCHAIN_SHARD: &str = "-9223372036854775808";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 pub struct BlockId {
     pub file_hash: String,
     pub root_hash: String,
@@ -38,11 +60,42 @@ pub struct Transaction {
     pub other_fee: String,
     pub storage_fee: String,
     pub utime: usize,
+    /// Set by tonlib versions listing block transactions with `blocks.getTransactionsExt`.
+    #[serde(default)]
+    pub address: Option<TransactionMessageAddress>,
 
     pub in_msg: TransactionMessage,
     pub out_msgs: Vec<TransactionMessage>,
 }
 
+impl Transaction {
+    /// Returns the `blocks.getTransactions` cursor pointing at this transaction,
+    /// `None` without `address` or with a malformed one.
+    pub fn account_transaction_id(&self) -> Option<AccountTransactionId> {
+        let account = account_id(&self.address.as_ref()?.account_address)?;
+        Some(AccountTransactionId {
+            account: base64::encode(account),
+            lt: self.transaction_id.lt.parse().ok()?,
+            hash: self.transaction_id.hash.clone(),
+        })
+    }
+}
+
+/// Extracts the 32 byte account id from a raw (`0:ab12..`) or user-friendly address.
+fn account_id(address: &str) -> Option<Vec<u8>> {
+    if let Some((_, hex_id)) = address.split_once(':') {
+        return hex::decode(hex_id).ok().filter(|id| id.len() == 32);
+    }
+    let decoded = base64::decode_config(address, base64::URL_SAFE)
+        .or_else(|_| base64::decode(address))
+        .ok()?;
+    // flags, workchain, account id, crc16
+    if decoded.len() != 36 {
+        return None;
+    }
+    return Some(decoded[2..34].to_vec());
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct TransactionMessage {
     destination: TransactionMessageAddress,
@@ -69,6 +122,16 @@ pub struct TransactionMessageAddress {
     pub account_address: String,
 }
 
+/// The lite server tonlib is connected to.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct LiteServerInfo {
+    /// Unix time of the server.
+    pub now: i64,
+    pub version: i32,
+    #[serde(deserialize_with = "deserialize_int64")]
+    pub capabilities: u64,
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct MasterChainInfo {
     pub init: BlockId,
@@ -77,7 +140,156 @@ pub struct MasterChainInfo {
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct Shards {
+    pub shards: Vec<BlockId>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BlockHeader {
+    pub id: BlockId,
+    pub global_id: i32,
+    pub version: i32,
+    pub after_merge: bool,
+    pub after_split: bool,
+    pub before_split: bool,
+    pub want_merge: bool,
+    pub want_split: bool,
+    pub is_key_block: bool,
+    pub min_ref_mc_seqno: i32,
+    pub prev_key_block_seqno: i32,
+    pub start_lt: String,
+    pub end_lt: String,
+    pub gen_utime: i64,
+    /// One block, or two right after a shard merge.
+    pub prev_blocks: Vec<BlockId>,
+}
+
+/// A transaction of a block, as listed by `blocks.getTransactions`.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct AccountTransactionId {
     pub account: String,
+    #[serde(deserialize_with = "deserialize_int64")]
     pub lt: usize,
+    #[serde(default)]
+    pub hash: String,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BlockTransactions {
+    pub id: BlockId,
+    pub req_count: i32,
+    /// More transactions follow the last one.
+    pub incomplete: bool,
+    pub transactions: Vec<AccountTransactionId>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct BlockTransactionsExt {
+    pub id: BlockId,
+    pub req_count: i32,
+    /// More transactions follow the last one.
+    pub incomplete: bool,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Reads a tonlib `int64`, sent as a string.
+fn deserialize_int64<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr + serde::Deserialize<'de>,
+    T::Err: std::fmt::Display,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Int64<T> {
+        String(String),
+        Number(T),
+    }
+
+    return match serde::Deserialize::deserialize(deserializer)? {
+        Int64::String(value) => value.parse().map_err(serde::de::Error::custom),
+        Int64::Number(value) => Ok(value),
+    };
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ExtMessageInfo {
+    /// Base64 hash of the message cell.
+    pub hash: String,
+}
+
+/// tonlib's `ok` answer for requests without a result.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct Empty {}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct AccountAddress {
+    pub account_address: String,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct UnpackedAccountAddress {
+    pub workchain_id: i32,
+    pub bounceable: bool,
+    pub testnet: bool,
+    pub addr: String,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct Bip39Hints {
+    pub words: Vec<String>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct LogVerbosityLevel {
+    pub verbosity_level: i32,
+}
+
+/// An unsolicited tonlib message, not an answer to any request.
+#[derive(Debug, Clone)]
+pub enum Update {
+    SyncState(SyncState),
+    SendLiteServerQuery(SendLiteServerQuery),
+    Other(serde_json::Value),
+}
+
+impl Update {
+    /// Returns whether `value` is a tonlib `update*` object.
+    pub fn is_update(value: &serde_json::Value) -> bool {
+        return value["@type"]
+            .as_str()
+            .is_some_and(|t| t.starts_with("update"));
+    }
+
+    pub fn from_value(value: serde_json::Value) -> Self {
+        let update = match value["@type"].as_str() {
+            Some("updateSyncState") => {
+                serde_json::from_value(value["sync_state"].clone()).map(Update::SyncState)
+            }
+            Some("updateSendLiteServerQuery") => {
+                serde_json::from_value(value.clone()).map(Update::SendLiteServerQuery)
+            }
+            _ => return Update::Other(value),
+        };
+        return update.unwrap_or(Update::Other(value));
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "@type")]
+pub enum SyncState {
+    #[serde(rename = "syncStateDone")]
+    Done,
+    #[serde(rename = "syncStateInProgress")]
+    InProgress {
+        from_seqno: i32,
+        to_seqno: i32,
+        current_seqno: i32,
+    },
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SendLiteServerQuery {
+    pub id: String,
+    pub data: String,
 }
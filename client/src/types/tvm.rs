@@ -0,0 +1,264 @@
+use num_bigint::BigInt;
+use serde_json::json;
+
+/// A TVM stack value, as passed to and returned from get-methods.
+///
+/// Cells and slices hold the serialized BoC.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "serde_json::Value", into = "serde_json::Value")]
+pub enum TvmStackEntry {
+    Number(BigInt),
+    Cell(Vec<u8>),
+    Slice(Vec<u8>),
+    Tuple(Vec<TvmStackEntry>),
+    List(Vec<TvmStackEntry>),
+    /// A value tonlib can't represent, like a continuation.
+    Unsupported,
+}
+
+impl TvmStackEntry {
+    pub fn as_number(&self) -> Option<&BigInt> {
+        match self {
+            Self::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// Returns the BoC of a cell or slice.
+    pub fn as_boc(&self) -> Option<&[u8]> {
+        match self {
+            Self::Cell(boc) | Self::Slice(boc) => Some(boc),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of a tuple or list.
+    pub fn as_elements(&self) -> Option<&[TvmStackEntry]> {
+        match self {
+            Self::Tuple(elements) | Self::List(elements) => Some(elements),
+            _ => None,
+        }
+    }
+}
+
+impl From<BigInt> for TvmStackEntry {
+    fn from(number: BigInt) -> Self {
+        Self::Number(number)
+    }
+}
+
+impl From<i64> for TvmStackEntry {
+    fn from(number: i64) -> Self {
+        Self::Number(number.into())
+    }
+}
+
+impl From<TvmStackEntry> for serde_json::Value {
+    fn from(entry: TvmStackEntry) -> Self {
+        match entry {
+            TvmStackEntry::Number(number) => json!({
+              "@type": "tvm.stackEntryNumber",
+              "number": { "@type": "tvm.numberDecimal", "number": number.to_string() }
+            }),
+            TvmStackEntry::Cell(boc) => json!({
+              "@type": "tvm.stackEntryCell",
+              "cell": { "@type": "tvm.cell", "bytes": base64::encode(boc) }
+            }),
+            TvmStackEntry::Slice(boc) => json!({
+              "@type": "tvm.stackEntrySlice",
+              "slice": { "@type": "tvm.slice", "bytes": base64::encode(boc) }
+            }),
+            TvmStackEntry::Tuple(elements) => json!({
+              "@type": "tvm.stackEntryTuple",
+              "tuple": { "@type": "tvm.tuple", "elements": elements }
+            }),
+            TvmStackEntry::List(elements) => json!({
+              "@type": "tvm.stackEntryList",
+              "list": { "@type": "tvm.list", "elements": elements }
+            }),
+            TvmStackEntry::Unsupported => json!({ "@type": "tvm.stackEntryUnsupported" }),
+        }
+    }
+}
+
+impl TryFrom<serde_json::Value> for TvmStackEntry {
+    type Error = String;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let entry = match value["@type"].as_str().unwrap_or_default() {
+            "tvm.stackEntryNumber" => {
+                let number = value["number"]["number"]
+                    .as_str()
+                    .ok_or("tvm number without value")?;
+                Self::Number(
+                    number
+                        .parse()
+                        .map_err(|e| format!("bad tvm number {number}: {e}"))?,
+                )
+            }
+            "tvm.stackEntryCell" => Self::Cell(bytes(&value["cell"]["bytes"])?),
+            "tvm.stackEntrySlice" => Self::Slice(bytes(&value["slice"]["bytes"])?),
+            "tvm.stackEntryTuple" => Self::Tuple(elements(&value["tuple"]["elements"])?),
+            "tvm.stackEntryList" => Self::List(elements(&value["list"]["elements"])?),
+            "tvm.stackEntryUnsupported" => Self::Unsupported,
+            other => return Err(format!("unknown tvm stack entry {other}")),
+        };
+        return Ok(entry);
+    }
+}
+
+fn bytes(value: &serde_json::Value) -> Result<Vec<u8>, String> {
+    let encoded = value.as_str().ok_or("tvm cell without bytes")?;
+    return base64::decode(encoded).map_err(|e| format!("bad tvm cell bytes: {e}"));
+}
+
+fn elements(value: &serde_json::Value) -> Result<Vec<TvmStackEntry>, String> {
+    return serde_json::from_value(value.clone()).map_err(|e| e.to_string());
+}
+
+/// tonlib's handle of a smart contract loaded with `smc.load`.
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct SmcInfo {
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SmcRunResult {
+    pub gas_used: i64,
+    pub stack: Vec<TvmStackEntry>,
+    /// TVM exit code, 0 and 1 mean success.
+    pub exit_code: i32,
+}
+
+impl SmcRunResult {
+    pub fn is_success(&self) -> bool {
+        return self.exit_code == 0 || self.exit_code == 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(entry: TvmStackEntry, expected: serde_json::Value) {
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(
+            serde_json::from_value::<TvmStackEntry>(value).unwrap(),
+            entry
+        );
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        for number in ["0", "-1", "340282366920938463463374607431768211457"] {
+            round_trip(
+                TvmStackEntry::Number(number.parse().unwrap()),
+                json!({
+                  "@type": "tvm.stackEntryNumber",
+                  "number": { "@type": "tvm.numberDecimal", "number": number }
+                }),
+            );
+        }
+        let negative: TvmStackEntry = (-42).into();
+        assert_eq!(negative.as_number(), Some(&BigInt::from(-42)));
+    }
+
+    #[test]
+    fn cells_and_slices_round_trip() {
+        let boc = vec![0xb5, 0xee, 0x9c, 0x72, 0x01];
+        round_trip(
+            TvmStackEntry::Cell(boc.clone()),
+            json!({
+              "@type": "tvm.stackEntryCell",
+              "cell": { "@type": "tvm.cell", "bytes": "te6ccgE=" }
+            }),
+        );
+        round_trip(
+            TvmStackEntry::Slice(boc.clone()),
+            json!({
+              "@type": "tvm.stackEntrySlice",
+              "slice": { "@type": "tvm.slice", "bytes": "te6ccgE=" }
+            }),
+        );
+        assert_eq!(TvmStackEntry::Slice(boc.clone()).as_boc(), Some(&boc[..]));
+    }
+
+    #[test]
+    fn nested_tuples_and_lists_round_trip() {
+        let entry = TvmStackEntry::Tuple(vec![
+            1.into(),
+            TvmStackEntry::List(vec![
+                TvmStackEntry::Cell(vec![1]),
+                TvmStackEntry::Unsupported,
+            ]),
+        ]);
+        round_trip(
+            entry.clone(),
+            json!({
+              "@type": "tvm.stackEntryTuple",
+              "tuple": {
+                "@type": "tvm.tuple",
+                "elements": [
+                  {
+                    "@type": "tvm.stackEntryNumber",
+                    "number": { "@type": "tvm.numberDecimal", "number": "1" }
+                  },
+                  {
+                    "@type": "tvm.stackEntryList",
+                    "list": {
+                      "@type": "tvm.list",
+                      "elements": [
+                        {
+                          "@type": "tvm.stackEntryCell",
+                          "cell": { "@type": "tvm.cell", "bytes": "AQ==" }
+                        },
+                        { "@type": "tvm.stackEntryUnsupported" }
+                      ]
+                    }
+                  }
+                ]
+              }
+            }),
+        );
+        assert_eq!(entry.as_elements().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        let malformed = [
+            json!({ "@type": "tvm.stackEntryContinuation" }),
+            json!({}),
+            json!({ "@type": "tvm.stackEntryNumber", "number": {} }),
+            json!({
+              "@type": "tvm.stackEntryNumber",
+              "number": { "@type": "tvm.numberDecimal", "number": "1e3" }
+            }),
+            json!({ "@type": "tvm.stackEntryCell", "cell": { "bytes": "not base64!" } }),
+            json!({ "@type": "tvm.stackEntryTuple", "tuple": { "elements": [{}] } }),
+        ];
+        for value in malformed {
+            assert!(
+                serde_json::from_value::<TvmStackEntry>(value.clone()).is_err(),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn run_result_exit_codes() {
+        let result = |exit_code: i32| -> SmcRunResult {
+            serde_json::from_value(json!({
+              "@type": "smc.runResult",
+              "gas_used": 100,
+              "stack": [],
+              "exit_code": exit_code
+            }))
+            .unwrap()
+        };
+        assert!(result(0).is_success());
+        assert!(result(1).is_success());
+        assert!(!result(2).is_success());
+        assert!(!result(-14).is_success());
+    }
+}
@@ -0,0 +1,111 @@
+use super::Key;
+
+/// Default `wallet_id` of v3 and highload v2 wallets in the basechain.
+pub const DEFAULT_WALLET_ID: i64 = 698983191;
+
+/// Code and data a wallet is deployed with, its address is derived from them.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "@type")]
+pub enum InitialAccountState {
+    #[serde(rename = "wallet.v3.initialAccountState")]
+    WalletV3 { public_key: String, wallet_id: i64 },
+    #[serde(rename = "wallet.highload.v2.initialAccountState")]
+    HighloadV2 { public_key: String, wallet_id: i64 },
+}
+
+impl InitialAccountState {
+    pub fn wallet_v3(key: &Key) -> Self {
+        Self::WalletV3 {
+            public_key: key.public_key.clone(),
+            wallet_id: DEFAULT_WALLET_ID,
+        }
+    }
+
+    pub fn highload_v2(key: &Key) -> Self {
+        Self::HighloadV2 {
+            public_key: key.public_key.clone(),
+            wallet_id: DEFAULT_WALLET_ID,
+        }
+    }
+}
+
+/// Payload of an outgoing wallet message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageData {
+    /// A plain text comment, no payload when empty.
+    Text(String),
+    /// A text comment tonlib encrypts for the destination.
+    EncryptedText(String),
+    /// A body cell and an optional state init, both serialized BoCs.
+    Raw { body: Vec<u8>, init_state: Vec<u8> },
+}
+
+/// An internal message sent by a wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub destination: String,
+    /// Amount in nanotons.
+    pub amount: i64,
+    pub data: MessageData,
+    /// Destination public key for `MessageData::EncryptedText`, tonlib takes it
+    /// from the destination wallet when empty.
+    pub public_key: String,
+    pub send_mode: i32,
+}
+
+impl Message {
+    /// A message without payload, paying forward fees separately (send mode 3).
+    pub fn new(destination: &str, amount: i64) -> Self {
+        Self {
+            destination: destination.to_owned(),
+            amount,
+            data: MessageData::Text(String::new()),
+            public_key: String::new(),
+            send_mode: 3,
+        }
+    }
+
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.data = MessageData::Text(comment.to_owned());
+        return self;
+    }
+
+    pub fn with_encrypted_comment(mut self, comment: &str) -> Self {
+        self.data = MessageData::EncryptedText(comment.to_owned());
+        return self;
+    }
+}
+
+/// A wallet query prepared by `createQuery`, kept by tonlib until `query.forget`.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct QueryInfo {
+    pub id: i64,
+    /// Unix time after which the wallet rejects the message.
+    pub valid_until: i64,
+    /// Base64 hash of the external message body.
+    pub body_hash: String,
+    pub body: String,
+    pub init_state: String,
+}
+
+/// Fees in nanotons.
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct Fees {
+    pub in_fwd_fee: i64,
+    pub storage_fee: i64,
+    pub gas_fee: i64,
+    pub fwd_fee: i64,
+}
+
+impl Fees {
+    pub fn total(&self) -> i64 {
+        return self.in_fwd_fee + self.storage_fee + self.gas_fee + self.fwd_fee;
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct QueryFees {
+    pub source_fees: Fees,
+    /// One entry per message, in order.
+    pub destination_fees: Vec<Fees>,
+}